@@ -1,9 +1,60 @@
 use regex::Regex;
 
+/// How `do()` and `don't()` instructions are interpreted
+#[derive(PartialEq, Debug, Copy, Clone)]
+enum PreprocessorMode {
+    // `don't()` and `do()` toggle a single flag (the puzzle rules)
+    Flat,
+    // Every `don't()` must be matched by its own `do()` before `mul` is enabled again
+    Nested,
+}
+
+#[derive(PartialEq, Debug, Copy, Clone)]
+struct PreprocessorOptions {
+    mode: PreprocessorMode,
+    initially_enabled: bool,
+}
+
+impl Default for PreprocessorOptions {
+    fn default() -> Self {
+        PreprocessorOptions {
+            mode: PreprocessorMode::Flat,
+            initially_enabled: true,
+        }
+    }
+}
+
 fn main() {
     let input = include_str!("input.txt");
+    let options = parse_args(std::env::args().skip(1));
     println!("Part 1: {}", parse_and_sum(input));
-    println!("Part 2: {}", parse_preprocessor_and_sum(input));
+    if options == PreprocessorOptions::default() {
+        println!("Part 2: {}", parse_preprocessor_and_sum(input));
+    } else {
+        println!(
+            "Part 2 ({:?}): {}",
+            options,
+            parse_with_options(input, options)
+        );
+    }
+}
+
+/// Parse the command line, e.g. `day3 nested --start-disabled`
+fn parse_args(args: impl Iterator<Item = String>) -> PreprocessorOptions {
+    let mut options = PreprocessorOptions::default();
+    for arg in args {
+        match arg.as_str() {
+            "flat" => options.mode = PreprocessorMode::Flat,
+            "nested" => options.mode = PreprocessorMode::Nested,
+            "--start-enabled" => options.initially_enabled = true,
+            "--start-disabled" => options.initially_enabled = false,
+            _ => panic!(
+                "Invalid argument '{}', expected: [flat|nested] [--start-enabled|--start-disabled]",
+                arg
+            ),
+        }
+    }
+    options
 }
 
 fn parse_and_sum(s: &str) -> u32 {
@@ -43,6 +94,32 @@ fn parse_preprocessor_and_sum(mut s: &str) -> u32 {
     sum
 }
 
+/// Sum all enabled `mul` instructions using the given interpretation of `do()` and `don't()`
+fn parse_with_options(s: &str, options: PreprocessorOptions) -> u32 {
+    let pattern = Regex::new(r"mul\(([0-9]{1,3}),([0-9]{1,3})\)|do\(\)|don't\(\)").unwrap();
+
+    // Number of `don't()` not yet matched by a `do()`; `mul` is enabled only when zero
+    let mut depth: u32 = if options.initially_enabled { 0 } else { 1 };
+    let mut sum = 0;
+    for cap in pattern.captures_iter(s) {
+        match &cap[0] {
+            "do()" => depth = depth.saturating_sub(1),
+            "don't()" => match options.mode {
+                PreprocessorMode::Flat => depth = 1,
+                PreprocessorMode::Nested => depth += 1,
+            },
+            _ => {
+                if depth == 0 {
+                    let left = cap[1].parse::<u32>().unwrap();
+                    let right = cap[2].parse::<u32>().unwrap();
+                    sum += left * right;
+                }
+            }
+        }
+    }
+    sum
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -62,4 +139,47 @@ mod tests {
         );
         assert_eq!(result, 48);
     }
+
+    #[test]
+    fn test_options() {
+        let input = "xmul(2,4)&mul[3,7]!^don't()_mul(5,5)+mul(32,64](mul(11,8)undo()?mul(8,5))";
+        let flat = PreprocessorOptions::default();
+        assert_eq!(parse_with_options(input, flat), 48);
+
+        let nested = PreprocessorOptions {
+            mode: PreprocessorMode::Nested,
+            initially_enabled: true,
+        };
+        assert_eq!(parse_with_options(input, nested), 48);
+
+        // The second `don't()` needs its own `do()`
+        let input = "mul(1,1)don't()mul(2,2)don't()mul(3,3)do()mul(4,4)do()mul(5,5)";
+        assert_eq!(parse_with_options(input, flat), 1 + 16 + 25);
+        assert_eq!(parse_with_options(input, nested), 1 + 25);
+
+        // Extra `do()` are ignored
+        let input = "do()do()mul(1,1)don't()mul(2,2)do()mul(3,3)";
+        assert_eq!(parse_with_options(input, nested), 1 + 9);
+
+        // Initially disabled
+        let input = "mul(1,1)do()mul(2,2)";
+        let disabled = PreprocessorOptions {
+            mode: PreprocessorMode::Flat,
+            initially_enabled: false,
+        };
+        assert_eq!(parse_with_options(input, disabled), 4);
+        assert_eq!(parse_with_options(input, flat), 5);
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let args = ["nested", "--start-disabled"].map(String::from);
+        let options = parse_args(args.into_iter());
+        assert_eq!(options.mode, PreprocessorMode::Nested);
+        assert!(!options.initially_enabled);
+        assert_eq!(
+            parse_args(std::iter::empty()),
+            PreprocessorOptions::default()
+        );
+    }
 }
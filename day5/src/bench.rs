@@ -0,0 +1,113 @@
+use crate::rules::Rules;
+use crate::{is_correct_order, InputFile};
+use std::collections::HashSet;
+use std::time::Instant;
+
+/// Compare the indexed ordering check against the previous pairwise scan on a large synthetic input
+pub fn run() {
+    let input = generate_input(5000, 200, 2000, 101, 42);
+    println!(
+        "Synthetic input: {} rules, {} updates of {} pages",
+        input.ordering.len(),
+        input.updates.len(),
+        input.updates[0].len()
+    );
+
+    let pairs: HashSet<(i32, i32)> = input.ordering.iter().copied().collect();
+    let start = Instant::now();
+    let pairwise = (0..input.updates.len())
+        .filter(|&i| is_correct_order_pairwise(&pairs, &input.updates[i]))
+        .count();
+    let pairwise_time = start.elapsed();
+
+    let start = Instant::now();
+    let indexed = (0..input.updates.len())
+        .filter(|&i| is_correct_order(&input, i))
+        .count();
+    let indexed_time = start.elapsed();
+
+    assert_eq!(pairwise, indexed, "The implementations disagree");
+    println!("Correct updates: {}", indexed);
+    println!("Pairwise scan: {:?}", pairwise_time);
+    println!("Indexed check: {:?}", indexed_time);
+}
+
+/// The original check, looking up every pair of pages in a flat set of rules
+pub fn is_correct_order_pairwise(ordering: &HashSet<(i32, i32)>, update: &[i32]) -> bool {
+    for i in 0..update.len() {
+        for j in i + 1..update.len() {
+            if ordering.contains(&(update[j], update[i])) {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+/// Generate a consistent input: pages are ranked by a hidden permutation and there is a rule
+/// between every pair of pages whose ranks are closer than `window`.
+/// Each update picks `update_len` pages within a window; about half of them are then shuffled.
+pub fn generate_input(
+    num_pages: usize,
+    window: usize,
+    num_updates: usize,
+    update_len: usize,
+    seed: u64,
+) -> InputFile {
+    assert!(update_len <= window && window <= num_pages);
+    let mut random = Lcg(seed);
+
+    // ranking[r] is the page with rank r
+    let mut ranking: Vec<i32> = (0..num_pages as i32).collect();
+    random.shuffle(&mut ranking);
+
+    let mut ordering = Rules::default();
+    for i in 0..num_pages {
+        for j in i + 1..(i + window).min(num_pages) {
+            ordering.insert(ranking[i], ranking[j]);
+        }
+    }
+
+    let mut updates = vec![];
+    for _ in 0..num_updates {
+        let start = random.below(num_pages - window + 1);
+        let mut ranks: Vec<usize> = (start..start + window).collect();
+        random.shuffle(&mut ranks);
+        ranks.truncate(update_len);
+        ranks.sort();
+        let mut update: Vec<i32> = ranks.iter().map(|&r| ranking[r]).collect();
+        if random.below(2) == 0 {
+            random.shuffle(&mut update);
+        }
+        updates.push(update);
+    }
+
+    InputFile {
+        rules: ordering.iter().copied().collect(),
+        ordering,
+        updates,
+    }
+}
+
+/// Minimal deterministic pseudo-random generator, good enough for synthetic inputs
+pub struct Lcg(pub u64);
+
+impl Lcg {
+    pub fn next(&mut self) -> u64 {
+        self.0 = self
+            .0
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        self.0 >> 33
+    }
+
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    pub fn shuffle<T>(&mut self, values: &mut [T]) {
+        for i in (1..values.len()).rev() {
+            values.swap(i, self.below(i + 1));
+        }
+    }
+}
@@ -1,20 +1,82 @@
-use std::collections::HashSet;
+mod bench;
+mod parser;
+mod repair;
+mod rules;
+mod writer;
 
-struct InputFile {
-    ordering: HashSet<(i32, i32)>,
-    updates: Vec<Vec<i32>>,
+use parser::parse_file;
+use rules::{Page, Rules};
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::{Display, Formatter};
+use writer::{write_file, WriteOptions};
+
+#[derive(Debug)]
+struct InputFile<P = i32> {
+    ordering: Rules<P>,
+    // Rules in the order they were read, repeats included, so the file can be written back as is
+    rules: Vec<(P, P)>,
+    updates: Vec<Vec<P>>,
+}
+
+impl<P: Page> PartialEq for InputFile<P> {
+    /// Inputs are equal when they have the same rules and updates, however the rules were written
+    fn eq(&self, other: &Self) -> bool {
+        self.ordering == other.ordering && self.updates == other.updates
+    }
 }
 
 fn main() {
-    let input = parse_file(include_str!("input.txt"));
-    println!("Part 1: {}", do_part1(&input));
-    println!("Part 2: {}", do_part2(&input));
+    let input = match parse_file(include_str!("input.txt")) {
+        Ok(input) => input,
+        Err(err) => {
+            eprintln!("Invalid input: {}", err);
+            std::process::exit(1);
+        }
+    };
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(|x| x.as_str()) {
+        None => {
+            report_inconsistencies(&input);
+            println!("Part 1: {}", do_part1(&input));
+            println!("Part 2: {}", do_part2(&input));
+        }
+        Some("report") => print!("{}", violation_report(&input)),
+        Some("rules") => print!("{}", rules_report(&input.ordering)),
+        Some("precedes") => {
+            let before = args.get(1).and_then(|x| x.parse().ok()).expect("Expected page");
+            let after = args.get(2).and_then(|x| x.parse().ok()).expect("Expected page");
+            println!("{}", input.ordering.must_precede(&before, &after));
+        }
+        Some("closure") => {
+            let pages = args.get(1).expect("Expected comma-separated pages");
+            let pages: Vec<i32> = pages.split(",").map(|x| x.parse().unwrap()).collect();
+            let closure = input.ordering.restrict(&pages).closure();
+            let mut closure: Vec<_> = closure.iter().collect();
+            closure.sort();
+            for (before, after) in closure {
+                println!("{}|{}", before, after);
+            }
+        }
+        Some("repair") => print!("{}", repair_report(&input)),
+        Some("normalize") => {
+            let options = WriteOptions {
+                sorted: true,
+                deduplicated: true,
+            };
+            print!("{}", write_file(&input, options));
+        }
+        Some("bench") => bench::run(),
+        Some(_) => panic!(
+            "Invalid arguments {:?}, expected: report | rules | precedes X Y | closure X,Y,... | repair | normalize | bench",
+            args
+        ),
+    }
 }
 
 fn do_part1(input: &InputFile) -> i32 {
     let mut sum = 0;
     for (i, update) in input.updates.iter().enumerate() {
-        if is_correct_order(&input, i) {
+        if is_correct_order(input, i) {
             sum += get_middle_point(update);
         }
     }
@@ -24,97 +86,301 @@ fn do_part1(input: &InputFile) -> i32 {
 fn do_part2(input: &InputFile) -> i32 {
     let mut sum = 0;
     for (i, _) in input.updates.iter().enumerate() {
-        if !is_correct_order(&input, i) {
-            let correct_update = fix_order(&input, i);
+        if !is_correct_order(input, i) {
+            let correct_update = fix_order(input, i);
             sum += get_middle_point(&correct_update);
         }
     }
     sum
 }
 
-fn get_middle_point(update: &Vec<i32>) -> i32 {
+fn get_middle_point(update: &[i32]) -> i32 {
     update[update.len() / 2]
 }
 
-fn parse_file(input: &str) -> InputFile {
-    let mut iterator = input.lines().into_iter();
+/// Check that no rule is broken by the update.
+/// When the rules are acyclic and every pair of adjacent pages is covered by a rule (as in
+/// consistent inputs), this only takes a linear number of lookups. Otherwise each page is checked
+/// against its predecessors or against the pages after it, whichever are fewer.
+fn is_correct_order<P: Page>(file: &InputFile<P>, update_id: usize) -> bool {
+    assert!(update_id < file.updates.len());
 
-    let mut ordering = HashSet::new();
-    loop {
-        let line = iterator.next().expect("Unexpected EOF").trim();
-        if line.is_empty() {
-            break;
+    let update = &file.updates[update_id];
+    let mut chained = true;
+    for pair in update.windows(2) {
+        if file.ordering.contains(&pair[1], &pair[0]) {
+            return false;
         }
-        let mut pair = line.split("|").map(|x| x.parse::<i32>().unwrap());
-        let left = pair.next().expect("Expected value");
-        let right = pair.next().expect("Expected value");
-        ordering.insert((left, right));
+        chained &= file.ordering.contains(&pair[0], &pair[1]);
+    }
+    if chained && file.ordering.is_acyclic() {
+        // All the pages are linked by a chain of rules, a violation would close a cycle
+        return true;
     }
 
-    let mut updates = vec![];
-    loop {
-        let line = iterator.next();
-        if line.is_none() {
-            break;
+    // Look for any page followed by one of its predecessors
+    let positions: HashMap<&P, usize> = update.iter().enumerate().map(|(i, x)| (x, i)).collect();
+    update.iter().enumerate().all(|(i, page)| {
+        let after = &update[i + 1..];
+        if file.ordering.num_predecessors(page) < after.len() {
+            file.ordering
+                .predecessors(page)
+                .all(|x| positions.get(x).is_none_or(|&j| j < i))
+        } else {
+            after.iter().all(|x| !file.ordering.contains(x, page))
         }
-        updates.push(
-            line.unwrap()
-                .split(",")
-                .map(|x| x.parse::<i32>().unwrap())
-                .collect::<Vec<i32>>(),
-        );
-    }
+    })
+}
 
-    InputFile { ordering, updates }
+/// A rule `X|Y` broken by an update because Y appears before X
+#[derive(PartialEq, Debug)]
+struct Violation<P = i32> {
+    rule: (P, P),
+    // Positions of X and Y in the update
+    positions: (usize, usize),
 }
 
-fn is_correct_order(file: &InputFile, update_id: usize) -> bool {
-    assert!(update_id < file.updates.len());
+impl<P: Page> Display for Violation<P> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let (x, y) = &self.rule;
+        let (x_position, y_position) = self.positions;
+        write!(
+            f,
+            "{} at position {} must come before {} at position {} (rule {}|{})",
+            x, x_position, y, y_position, x, y
+        )
+    }
+}
 
-    let update = &file.updates[update_id];
+/// Find all the rules broken by an update, ordered by position of the first page
+fn find_violations<P: Page>(file: &InputFile<P>, update: &[P]) -> Vec<Violation<P>> {
+    let mut violations = vec![];
     for i in 0..update.len() {
         for j in i + 1..update.len() {
-            // If we have a constraint in the opposite direction, return false
-            if file.ordering.contains(&(update[j], update[i])) {
-                return false;
+            if file.ordering.contains(&update[j], &update[i]) {
+                violations.push(Violation {
+                    rule: (update[j].clone(), update[i].clone()),
+                    positions: (j, i),
+                });
+            }
+        }
+    }
+    violations
+}
+
+/// Human-readable report listing the violations of every update
+fn violation_report<P: Page>(file: &InputFile<P>) -> String {
+    let mut report = String::new();
+    for (i, update) in file.updates.iter().enumerate() {
+        let violations = find_violations(file, update);
+        let pages = update
+            .iter()
+            .map(|x| x.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        if violations.is_empty() {
+            report += &format!("Update {} ({}): ok\n", i, pages);
+        } else {
+            report += &format!(
+                "Update {} ({}): {} violation(s)\n",
+                i,
+                pages,
+                violations.len()
+            );
+            for violation in violations {
+                report += &format!("  {}\n", violation);
             }
         }
     }
+    report
+}
 
-    true
+/// Human-readable list of the moves needed to repair each incorrect update
+fn repair_report<P: Page>(file: &InputFile<P>) -> String {
+    let mut report = String::new();
+    for (i, update) in file.updates.iter().enumerate() {
+        if is_correct_order(file, i) {
+            continue;
+        }
+        match repair::repair_update(file, update) {
+            Ok(repair) => {
+                let pages: Vec<String> = repair.pages.iter().map(|x| x.to_string()).collect();
+                report += &format!(
+                    "Update {}: {} ({} move(s))\n",
+                    i,
+                    pages.join(","),
+                    repair.moves.len()
+                );
+                for x in repair.moves {
+                    match x.after {
+                        Some(after) => report += &format!("  move {} after {}\n", x.page, after),
+                        None => report += &format!("  move {} to the front\n", x.page),
+                    }
+                }
+            }
+            Err(cycle) => report += &format!("Update {}: cyclic rules {:?}\n", i, cycle.pages),
+        }
+    }
+    report
 }
 
-fn fix_order(file: &InputFile, update_id: usize) -> Vec<i32> {
+/// Human-readable summary of the rule set, listing the rules implied by the others
+fn rules_report<P: Page>(rules: &Rules<P>) -> String {
+    let redundant = rules.redundant_rules();
+    let minimized = rules.minimize();
+    let mut report = format!(
+        "{} rules, {} in the transitive closure, {} redundant, {} after minimization\n",
+        rules.len(),
+        rules.closure().len(),
+        redundant.len(),
+        minimized.len()
+    );
+    for (before, after) in redundant {
+        report += &format!("  {}|{}\n", before, after);
+    }
+    report
+}
+
+/// Sort the pages of an update so that every applicable rule is respected.
+/// Panics if the rules relevant to the update contain a cycle.
+fn fix_order<P: Page>(file: &InputFile<P>, update_id: usize) -> Vec<P> {
     assert!(update_id < file.updates.len());
 
-    let update = &file.updates[update_id];
-    let mut correct_update = vec![];
+    match sort_update(file, &file.updates[update_id]) {
+        Ok(sorted) => sorted.pages,
+        Err(cycle) => panic!("Update {} has cyclic rules: {:?}", update_id, cycle.pages),
+    }
+}
+
+#[derive(PartialEq, Debug)]
+struct SortedUpdate<P = i32> {
+    pages: Vec<P>,
+    // False if the rules allow more than one order; ties keep the original relative order
+    unique: bool,
+}
+
+#[derive(PartialEq, Debug)]
+struct CycleError<P = i32> {
+    // Pages forming the cycle, each one must come before the next (and the last before the first)
+    pages: Vec<P>,
+}
+
+/// Topological sort (Kahn's algorithm) of the pages using only the rules between them.
+fn sort_update<P: Page>(
+    file: &InputFile<P>,
+    update: &[P],
+) -> Result<SortedUpdate<P>, CycleError<P>> {
+    let to_pages = |x: Vec<usize>| x.iter().map(|&i| update[i].clone()).collect();
+    match topological_sort(&update_graph(file, update)) {
+        Ok((order, unique)) => Ok(SortedUpdate {
+            pages: to_pages(order),
+            unique,
+        }),
+        Err(cycle) => Err(CycleError {
+            pages: to_pages(cycle),
+        }),
+    }
+}
 
+/// The subgraph of the rules relevant to an update: for each position, the positions that must come after it
+fn update_graph<P: Page>(file: &InputFile<P>, update: &[P]) -> Vec<Vec<usize>> {
+    let positions: HashMap<&P, usize> = update.iter().enumerate().map(|(i, x)| (x, i)).collect();
+    let mut successors = vec![vec![]; update.len()];
     for i in 0..update.len() {
-        let page = update[i];
-        if correct_update.len() == 0 {
-            correct_update.push(page);
-        } else {
-            let index = correct_update
-                .iter()
-                .enumerate()
-                .find(|(_, value)| {
-                    // can I put `page` before the current value?
-                    let pair = (page, **value);
-                    return file.ordering.contains(&pair);
-                })
-                .map(|(j, _)| j)
-                .unwrap_or(correct_update.len());
-            correct_update.insert(index, page);
+        for page in file.ordering.successors(&update[i]) {
+            if let Some(&j) = positions.get(page) {
+                successors[i].push(j);
+            }
+        }
+        // Keep the traversal independent from the hashing order
+        successors[i].sort();
+    }
+    successors
+}
+
+/// Sort the nodes of a graph, returning the order and whether it is the only possible one.
+/// Ties are broken by picking the lowest node first. On failure, returns the nodes of a cycle.
+fn topological_sort(successors: &[Vec<usize>]) -> Result<(Vec<usize>, bool), Vec<usize>> {
+    let n = successors.len();
+    let mut in_degree = vec![0; n];
+    for &j in successors.iter().flatten() {
+        in_degree[j] += 1;
+    }
+
+    let mut available: BTreeSet<usize> = (0..n).filter(|&i| in_degree[i] == 0).collect();
+    let mut order = Vec::with_capacity(n);
+    let mut unique = true;
+    while let Some(i) = available.pop_first() {
+        if !available.is_empty() {
+            unique = false;
+        }
+        order.push(i);
+        for &j in &successors[i] {
+            in_degree[j] -= 1;
+            if in_degree[j] == 0 {
+                available.insert(j);
+            }
         }
     }
 
-    correct_update
+    if order.len() < n {
+        return Err(find_cycle(successors, &in_degree));
+    }
+    Ok((order, unique))
+}
+
+/// Extract a cycle from the nodes left over by Kahn's algorithm (those with a positive in-degree),
+/// starting from its lowest node.
+fn find_cycle(successors: &[Vec<usize>], in_degree: &[usize]) -> Vec<usize> {
+    let remaining = |i: usize| in_degree[i] > 0;
+    let mut predecessors = vec![vec![]; successors.len()];
+    for (i, next) in successors.iter().enumerate() {
+        for &j in next {
+            if remaining(i) && remaining(j) {
+                predecessors[j].push(i);
+            }
+        }
+    }
+
+    // A remaining node still has an edge from another remaining node, while it may have no
+    // remaining successor: walking backward must eventually repeat a node
+    let start = (0..in_degree.len()).find(|&i| remaining(i)).unwrap();
+    let mut visited_at = vec![None; in_degree.len()];
+    let mut path = vec![];
+    let mut current = start;
+    while visited_at[current].is_none() {
+        visited_at[current] = Some(path.len());
+        path.push(current);
+        current = predecessors[current][0];
+    }
+    let mut cycle = path.split_off(visited_at[current].unwrap());
+    cycle.reverse();
+    let lowest = (0..cycle.len()).min_by_key(|&i| cycle[i]).unwrap();
+    cycle.rotate_left(lowest);
+    cycle
+}
+
+/// Print to stderr every update whose rules are cyclic or do not determine a single order
+fn report_inconsistencies<P: Page>(file: &InputFile<P>) {
+    for (i, update) in file.updates.iter().enumerate() {
+        match sort_update(file, update) {
+            Ok(sorted) if !sorted.unique => {
+                eprintln!(
+                    "Update {} {:?}: order is not uniquely determined",
+                    i, update
+                )
+            }
+            Ok(_) => {}
+            Err(cycle) => eprintln!("Update {} {:?}: cyclic rules {:?}", i, update, cycle.pages),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use parser::ParseError;
+    use std::collections::HashSet;
 
     #[test]
     fn test_find() {
@@ -148,10 +414,11 @@ mod tests {
 61,13,29
 97,13,75,29,47"#
             .trim();
-        let input = parse_file(test_file);
+        let input = parse_file(test_file).unwrap();
         assert_eq!(input.ordering.len(), 21);
-        assert!(input.ordering.contains(&(47, 53)));
-        assert!(input.ordering.contains(&(53, 13)));
+        assert!(input.ordering.contains(&47, &53));
+        assert!(input.ordering.contains(&53, &13));
+        assert!(!input.ordering.contains(&13, &53));
 
         assert_eq!(input.updates.len(), 6);
         assert_eq!(input.updates[0], vec![75, 47, 61, 53, 29]);
@@ -171,5 +438,467 @@ mod tests {
         assert_eq!(fix_order(&input, 5), vec![97, 75, 47, 29, 13]);
 
         assert_eq!(do_part2(&input), 123);
+
+        // All the rules are given in the example, so the order is always unique
+        for update in &input.updates {
+            assert!(sort_update(&input, update).unwrap().unique);
+        }
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        // CRLF, missing separator and extra blank lines
+        let input = parse_file("\r\n1|2\r\n2|3\r\n1,2,3\r\n\r\n\r\n3,2,1\r\n\r\n").unwrap();
+        assert_eq!(input.ordering.len(), 2);
+        assert_eq!(input.updates, vec![vec![1, 2, 3], vec![3, 2, 1]]);
+
+        let input = parse_file("1|2\n\n\n").unwrap();
+        assert_eq!(input.updates.len(), 0);
+
+        let error = |input| parse_file(input).err().unwrap();
+        assert_eq!(
+            error("1|2\n1|x\n\n1,2,3"),
+            ParseError::InvalidPage {
+                line: 2,
+                value: "x".to_string()
+            }
+        );
+        assert_eq!(error("1|2|3"), ParseError::InvalidRule { line: 1 });
+        assert_eq!(
+            error("1|\n"),
+            ParseError::InvalidPage {
+                line: 1,
+                value: "".to_string()
+            }
+        );
+        assert_eq!(
+            error("1|2\n\n1,2,3\n3|4"),
+            ParseError::RuleAfterUpdates { line: 4 }
+        );
+        assert_eq!(
+            error("1|2\n\n1,2,3\n4,5,4"),
+            ParseError::DuplicatePage {
+                line: 4,
+                page: "4".to_string()
+            }
+        );
+        assert_eq!(
+            error("1|2\n\n1,2,3\n1,2"),
+            ParseError::EvenLength { line: 4, len: 2 }
+        );
+        assert_eq!(error("1|2\n\n1,,3").to_string(), "line 3: invalid page ''");
+    }
+
+    #[test]
+    fn test_write_file() {
+        let text = "47|53\n97|13\n47|53\n\n75,47,61\n97,61,53\n75,47,61\n";
+        let input = parse_file(text).unwrap();
+        assert_eq!(write_file(&input, WriteOptions::default()), text);
+        let options = WriteOptions {
+            sorted: true,
+            deduplicated: false,
+        };
+        assert_eq!(
+            write_file(&input, options),
+            "47|53\n47|53\n97|13\n\n75,47,61\n75,47,61\n97,61,53\n"
+        );
+        let options = WriteOptions {
+            sorted: true,
+            deduplicated: true,
+        };
+        assert_eq!(
+            write_file(&input, options),
+            "47|53\n97|13\n\n75,47,61\n97,61,53\n"
+        );
+
+        // The puzzle input is reproduced exactly
+        let text = include_str!("input.txt");
+        let input = parse_file(text).unwrap();
+        assert_eq!(
+            write_file(&input, WriteOptions::default()).trim_end(),
+            text.trim_end()
+        );
+    }
+
+    #[test]
+    fn test_write_round_trip() {
+        let mut random = bench::Lcg(1234);
+        for _ in 0..100 {
+            // Few pages, so that some rules are repeated
+            let mut rules = vec![];
+            for _ in 0..random.below(30) {
+                rules.push((random.below(8) as i32, random.below(8) as i32 - 2));
+            }
+            let ordering: Rules = rules.iter().copied().collect();
+            let mut updates = vec![];
+            for _ in 0..random.below(10) {
+                let mut pages: Vec<i32> = (-5..15).collect();
+                random.shuffle(&mut pages);
+                pages.truncate(random.below(10) * 2 + 1);
+                // Repeat some updates
+                let count = 1 + random.below(2);
+                updates.extend(std::iter::repeat_n(pages, count));
+            }
+            let input = InputFile {
+                ordering,
+                rules,
+                updates,
+            };
+
+            // Writing and parsing gives back the same input, written the same way
+            let text = write_file(&input, WriteOptions::default());
+            let parsed = parse_file(&text).unwrap();
+            assert_eq!(parsed, input);
+            assert_eq!(parsed.rules, input.rules);
+
+            // Normalization is idempotent and keeps the same rules and distinct updates
+            let options = WriteOptions {
+                sorted: true,
+                deduplicated: true,
+            };
+            let normalized = write_file(&input, options);
+            let parsed = parse_file(&normalized).unwrap();
+            assert_eq!(write_file(&parsed, options), normalized);
+            assert_eq!(parsed.ordering.len(), input.ordering.len());
+            assert!(input
+                .ordering
+                .iter()
+                .all(|(x, y)| parsed.ordering.contains(x, y)));
+            let distinct: HashSet<&Vec<i32>> = input.updates.iter().collect();
+            assert_eq!(parsed.updates.len(), distinct.len());
+            assert!(parsed.updates.iter().all(|x| distinct.contains(x)));
+        }
+    }
+
+    #[test]
+    fn test_string_pages() {
+        let text = "intro|body\nbody|outro\nintro|outro\n\nbody,intro,outro\nintro,body,outro";
+        let input: InputFile<String> = parser::parse_file_with(text).unwrap();
+        assert!(input
+            .ordering
+            .contains(&"intro".to_string(), &"body".to_string()));
+        assert!(!is_correct_order(&input, 0));
+        assert!(is_correct_order(&input, 1));
+        assert_eq!(fix_order(&input, 0), vec!["intro", "body", "outro"]);
+        assert_eq!(
+            find_violations(&input, &input.updates[0])[0].to_string(),
+            "intro at position 1 must come before body at position 0 (rule intro|body)"
+        );
+        assert_eq!(
+            write_file(&input, WriteOptions::default()),
+            text.to_string() + "\n"
+        );
+
+        // Numbers are still parsed as numbers
+        assert_eq!(
+            parse_file("1|2\n\nx,1,2").err().unwrap(),
+            ParseError::InvalidPage {
+                line: 3,
+                value: "x".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_is_correct_order() {
+        // Adjacent pages without a rule between them
+        let input = parse_file("3|1\n4|5\n\n1,2,3\n3,2,1\n4,2,5\n5,4,6\n1,6,7").unwrap();
+        let expected = [false, true, true, false, true];
+        for (i, &expected) in expected.iter().enumerate() {
+            assert_eq!(is_correct_order(&input, i), expected);
+        }
+
+        // Compare against the pairwise scan on a synthetic input
+        let input = bench::generate_input(300, 40, 200, 21, 7);
+        let pairs: HashSet<(i32, i32)> = input.ordering.iter().copied().collect();
+        let mut correct = 0;
+        for (i, update) in input.updates.iter().enumerate() {
+            let expected = bench::is_correct_order_pairwise(&pairs, update);
+            assert_eq!(is_correct_order(&input, i), expected);
+            correct += expected as usize;
+        }
+        assert!(correct > 0 && correct < input.updates.len());
+    }
+
+    #[test]
+    fn test_closure() {
+        let rules: Rules = [(1, 2), (2, 3), (1, 3), (3, 4), (5, 6)]
+            .into_iter()
+            .collect();
+        assert!(rules.must_precede(&1, &4));
+        assert!(rules.must_precede(&2, &4));
+        assert!(!rules.must_precede(&4, &1));
+        assert!(!rules.must_precede(&1, &5));
+        assert!(!rules.must_precede(&1, &1));
+
+        let closure = rules.closure();
+        let mut pairs: Vec<_> = closure.iter().copied().collect();
+        pairs.sort();
+        assert_eq!(
+            pairs,
+            vec![(1, 2), (1, 3), (1, 4), (2, 3), (2, 4), (3, 4), (5, 6)]
+        );
+
+        // Without page 2, only the direct rule 1|3 links 1 and 3
+        let closure = rules.restrict(&[1, 3, 4]).closure();
+        let mut pairs: Vec<_> = closure.iter().copied().collect();
+        pairs.sort();
+        assert_eq!(pairs, vec![(1, 3), (1, 4), (3, 4)]);
+        let closure = rules.restrict(&[1, 4]).closure();
+        assert_eq!(closure.len(), 0);
+
+        assert_eq!(rules.redundant_rules(), vec![(1, 3)]);
+        let minimized = rules.minimize();
+        assert_eq!(minimized.len(), 4);
+        assert!(!minimized.contains(&1, &3));
+        assert_eq!(minimized.closure().len(), rules.closure().len());
+    }
+
+    #[test]
+    fn test_minimize_cycle() {
+        // In a cycle every rule is implied by the others, but only one of them can be removed
+        let rules: Rules = [(1, 2), (2, 3), (3, 1), (1, 3)].into_iter().collect();
+        assert_eq!(rules.redundant_rules(), vec![(1, 3)]);
+        let rules: Rules = [(1, 2), (2, 1), (2, 3), (3, 2)].into_iter().collect();
+        assert_eq!(rules.redundant_rules(), vec![]);
+        let rules: Rules = [(1, 2), (2, 3), (3, 1), (1, 3), (3, 2)]
+            .into_iter()
+            .collect();
+        assert_eq!(rules.redundant_rules().len(), 3);
+        let minimized = rules.minimize();
+        assert_eq!(minimized.len(), 4);
+        assert_eq!(minimized.closure().len(), 9);
+    }
+
+    #[test]
+    fn test_rules_equality() {
+        let rules: Rules = [(1, 2), (2, 3), (1, 3)].into_iter().collect();
+        let reordered: Rules = [(1, 3), (2, 3), (1, 2)].into_iter().collect();
+        assert_eq!(rules, reordered);
+        assert_ne!(rules, [(1, 2), (2, 3)].into_iter().collect());
+
+        let minimized = rules.minimize();
+        assert_eq!(minimized, [(2, 3), (1, 2)].into_iter().collect());
+        assert_eq!(minimized.iter().collect::<Vec<_>>(), vec![&(1, 2), &(2, 3)]);
+    }
+
+    #[test]
+    fn test_violations() {
+        let input = parse_file("47|53\n97|13\n97|61\n97|47\n61|13\n\n97,61,13\n61,13,97").unwrap();
+        assert_eq!(find_violations(&input, &input.updates[0]), vec![]);
+
+        let violations = find_violations(&input, &input.updates[1]);
+        assert_eq!(
+            violations,
+            vec![
+                Violation {
+                    rule: (97, 61),
+                    positions: (2, 0)
+                },
+                Violation {
+                    rule: (97, 13),
+                    positions: (2, 1)
+                },
+            ]
+        );
+        assert_eq!(
+            violations[0].to_string(),
+            "97 at position 2 must come before 61 at position 0 (rule 97|61)"
+        );
+
+        assert_eq!(
+            violation_report(&input),
+            "\
+Update 0 (97,61,13): ok
+Update 1 (61,13,97): 2 violation(s)
+  97 at position 2 must come before 61 at position 0 (rule 97|61)
+  97 at position 2 must come before 13 at position 1 (rule 97|13)
+"
+        );
+    }
+
+    #[test]
+    fn test_repair() {
+        let input = parse_file("97|75\n75|47\n97|47\n\n75,97,47").unwrap();
+        let repair = repair::repair_update(&input, &input.updates[0]).unwrap();
+        assert_eq!(repair.pages, vec![97, 75, 47]);
+        assert_eq!(
+            repair.moves,
+            vec![repair::Move {
+                page: 75,
+                from: 0,
+                to: 1,
+                after: Some(97)
+            }]
+        );
+
+        // 2 ends up where it started, but only because 3 moved from before it to after it
+        let input = parse_file("1|2\n2|3\n1|3\n\n3,2,1").unwrap();
+        let repair = repair::repair_update(&input, &input.updates[0]).unwrap();
+        assert_eq!(repair.pages, vec![1, 2, 3]);
+        assert_eq!(
+            repair.moves,
+            vec![
+                repair::Move {
+                    page: 2,
+                    from: 1,
+                    to: 1,
+                    after: Some(1)
+                },
+                repair::Move {
+                    page: 3,
+                    from: 0,
+                    to: 2,
+                    after: Some(2)
+                }
+            ]
+        );
+        assert_eq!(
+            repair_report(&input),
+            "Update 0: 1,2,3 (2 move(s))\n  move 2 after 1\n  move 3 after 2\n"
+        );
+
+        // Moving 1 after 4 is enough, 5 stays in place
+        let input = parse_file("2|1\n3|1\n4|1\n\n1,2,3,4,5").unwrap();
+        let repair = repair::repair_update(&input, &input.updates[0]).unwrap();
+        assert_eq!(repair.pages, vec![2, 3, 4, 1, 5]);
+        assert_eq!(repair.moves.len(), 1);
+
+        // Already correct
+        let repair = repair::repair_update(&input, &[2, 1, 5]).unwrap();
+        assert_eq!(repair.pages, vec![2, 1, 5]);
+        assert_eq!(repair.moves, vec![]);
+
+        let input = parse_file("1|2\n2|3\n3|1\n\n3,2,1").unwrap();
+        assert!(repair::repair_update(&input, &input.updates[0]).is_err());
+    }
+
+    #[test]
+    fn test_repair_is_minimal() {
+        // Moving pages to go from one order to another takes `n - LCS` moves
+        fn moves_needed(from: &[i32], to: &[i32]) -> usize {
+            let mut lcs = vec![vec![0; to.len() + 1]; from.len() + 1];
+            for i in 0..from.len() {
+                for j in 0..to.len() {
+                    lcs[i + 1][j + 1] = if from[i] == to[j] {
+                        lcs[i][j] + 1
+                    } else {
+                        lcs[i][j + 1].max(lcs[i + 1][j])
+                    };
+                }
+            }
+            from.len() - lcs[from.len()][to.len()]
+        }
+
+        fn permutations(pages: &mut Vec<i32>, k: usize, result: &mut Vec<Vec<i32>>) {
+            if k == pages.len() {
+                result.push(pages.clone());
+            }
+            for i in k..pages.len() {
+                pages.swap(k, i);
+                permutations(pages, k + 1, result);
+                pages.swap(k, i);
+            }
+        }
+
+        let mut random = bench::Lcg(99);
+        for _ in 0..200 {
+            // Random acyclic rules: only from a lower to a higher page
+            let mut ordering = Rules::default();
+            for _ in 0..random.below(8) {
+                let x = random.below(6) as i32;
+                let y = random.below(6) as i32;
+                if x < y {
+                    ordering.insert(x, y);
+                }
+            }
+            let mut update: Vec<i32> = (0..6).collect();
+            random.shuffle(&mut update);
+            let input = InputFile {
+                rules: ordering.iter().copied().collect(),
+                ordering,
+                updates: vec![update.clone()],
+            };
+
+            let repair = repair::repair_update(&input, &update).unwrap();
+            assert!(find_violations(&input, &repair.pages).is_empty());
+            assert_eq!(moves_needed(&update, &repair.pages), repair.moves.len());
+
+            // Applying the moves in turn gives the repaired update
+            let mut replayed = update.clone();
+            for x in &repair.moves {
+                replayed.retain(|&page| page != x.page);
+                let to = x.after.map_or(0, |after| {
+                    replayed.iter().position(|&y| y == after).unwrap() + 1
+                });
+                replayed.insert(to, x.page);
+            }
+            assert_eq!(replayed, repair.pages);
+
+            let mut all = vec![];
+            permutations(&mut update.clone(), 0, &mut all);
+            let best = all
+                .iter()
+                .filter(|x| find_violations(&input, x).is_empty())
+                .map(|x| moves_needed(&update, x))
+                .min()
+                .unwrap();
+            assert_eq!(repair.moves.len(), best);
+        }
+    }
+
+    #[test]
+    fn test_sort_update() {
+        let input = parse_file("1|2\n2|3\n3|1\n4|5\n5|8\n\n5,8,4\n1,2,3\n6,5,4\n4,2,1").unwrap();
+
+        // Ties keep the original order
+        let sorted = sort_update(&input, &input.updates[2]).unwrap();
+        assert_eq!(sorted.pages, vec![6, 4, 5]);
+        assert!(!sorted.unique);
+
+        let sorted = sort_update(&input, &input.updates[0]).unwrap();
+        assert_eq!(sorted.pages, vec![4, 5, 8]);
+        assert!(sorted.unique);
+
+        // 1 -> 2 -> 3 -> 1
+        let cycle = sort_update(&input, &input.updates[1]).unwrap_err();
+        assert_eq!(cycle.pages, vec![1, 2, 3]);
+
+        // The cycle is only relevant if all its pages are in the update
+        let sorted = sort_update(&input, &input.updates[3]).unwrap();
+        assert_eq!(sorted.pages, vec![4, 1, 2]);
+    }
+
+    #[test]
+    fn test_is_correct_order_cycle() {
+        // Each pair of neighbours follows a rule, but 3|1 is broken
+        let input = parse_file("1|2\n2|3\n3|1\n\n1,2,3\n2,3,4\n3,1,2").unwrap();
+        assert!(!input.ordering.is_acyclic());
+        assert!(input.ordering.restrict(&[1, 2]).is_acyclic());
+        for (i, update) in input.updates.iter().enumerate() {
+            assert_eq!(
+                is_correct_order(&input, i),
+                find_violations(&input, update).is_empty()
+            );
+        }
+        assert!(!is_correct_order(&input, 0));
+        assert!(is_correct_order(&input, 1));
+    }
+
+    #[test]
+    fn test_cycle_with_sink() {
+        // 3 is left over by the sort because of 1, but it does not lead back into the cycle
+        let input = parse_file("1|2\n2|1\n1|3\n\n3,1,2").unwrap();
+        let cycle = sort_update(&input, &input.updates[0]).unwrap_err();
+        assert_eq!(cycle.pages, vec![1, 2]);
+        assert!(repair::repair_update(&input, &input.updates[0]).is_err());
+        assert_eq!(repair_report(&input), "Update 0: cyclic rules [1, 2]\n");
+    }
+
+    #[test]
+    #[should_panic(expected = "cyclic rules")]
+    fn test_fix_order_cycle() {
+        let input = parse_file("1|2\n2|1\n\n1,2,3").unwrap();
+        fix_order(&input, 0);
     }
 }
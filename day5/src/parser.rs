@@ -0,0 +1,122 @@
+use crate::rules::{Page, Rules};
+use crate::InputFile;
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// Errors found while parsing the input, line numbers start from 1
+#[derive(PartialEq, Debug)]
+pub enum ParseError {
+    // A page is not a valid number
+    InvalidPage { line: usize, value: String },
+    // A rule is not in the form `X|Y`
+    InvalidRule { line: usize },
+    // Rules must all be listed before the first update
+    RuleAfterUpdates { line: usize },
+    // The same page appears twice in an update
+    DuplicatePage { line: usize, page: String },
+    // Updates must have a middle page
+    EvenLength { line: usize, len: usize },
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::InvalidPage { line, value } => {
+                write!(f, "line {}: invalid page '{}'", line, value)
+            }
+            ParseError::InvalidRule { line } => {
+                write!(f, "line {}: expected a rule in the form X|Y", line)
+            }
+            ParseError::RuleAfterUpdates { line } => {
+                write!(f, "line {}: rules must come before the updates", line)
+            }
+            ParseError::DuplicatePage { line, page } => {
+                write!(f, "line {}: page {} appears more than once", line, page)
+            }
+            ParseError::EvenLength { line, len } => {
+                write!(
+                    f,
+                    "line {}: update has {} pages, expected an odd number",
+                    line, len
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Parse rules (`X|Y`) followed by updates (`A,B,C`) with numeric pages.
+/// Blank lines are ignored, so the separator between the two sections is optional.
+pub fn parse_file(input: &str) -> Result<InputFile, ParseError> {
+    parse_file_with(input)
+}
+
+/// Same as `parse_file`, with any kind of page identifier (e.g. `String`)
+pub fn parse_file_with<P: Page + FromStr>(input: &str) -> Result<InputFile<P>, ParseError> {
+    let mut ordering = Rules::default();
+    let mut rules = vec![];
+    let mut updates = vec![];
+
+    for (i, line) in input.lines().enumerate() {
+        let line_number = i + 1;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        if line.contains('|') {
+            if !updates.is_empty() {
+                return Err(ParseError::RuleAfterUpdates { line: line_number });
+            }
+            let (left, right) = line
+                .split_once('|')
+                .filter(|(_, right)| !right.contains('|'))
+                .ok_or(ParseError::InvalidRule { line: line_number })?;
+            let rule: (P, P) = (
+                parse_page(left, line_number)?,
+                parse_page(right, line_number)?,
+            );
+            ordering.insert(rule.0.clone(), rule.1.clone());
+            rules.push(rule);
+        } else {
+            let update = line
+                .split(',')
+                .map(|x| parse_page(x, line_number))
+                .collect::<Result<Vec<P>, _>>()?;
+            let mut pages = HashSet::new();
+            if let Some(page) = update.iter().find(|&x| !pages.insert(x)) {
+                return Err(ParseError::DuplicatePage {
+                    line: line_number,
+                    page: page.to_string(),
+                });
+            }
+            if update.len() % 2 == 0 {
+                return Err(ParseError::EvenLength {
+                    line: line_number,
+                    len: update.len(),
+                });
+            }
+            updates.push(update);
+        }
+    }
+
+    Ok(InputFile {
+        ordering,
+        rules,
+        updates,
+    })
+}
+
+fn parse_page<P: FromStr>(value: &str, line: usize) -> Result<P, ParseError> {
+    let value = value.trim();
+    let invalid = || ParseError::InvalidPage {
+        line,
+        value: value.to_string(),
+    };
+    if value.is_empty() {
+        return Err(invalid());
+    }
+    value.parse().map_err(|_| invalid())
+}
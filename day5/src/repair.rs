@@ -0,0 +1,175 @@
+use crate::rules::Page;
+use crate::{topological_sort, update_graph, CycleError, InputFile};
+use std::collections::VecDeque;
+
+/// A page taken out of an update and inserted back at another place.
+/// `from` and `to` are positions in the original and in the repaired update: the other moves shift
+/// the pages around, so a page can be moved and keep the same position.
+#[derive(PartialEq, Debug)]
+pub struct Move<P = i32> {
+    pub page: P,
+    // Position in the original update
+    pub from: usize,
+    // Position in the repaired update
+    pub to: usize,
+    // Page right before it in the repaired update, none if it goes first
+    pub after: Option<P>,
+}
+
+#[derive(PartialEq, Debug)]
+pub struct Repair<P = i32> {
+    pub pages: Vec<P>,
+    // Moves sorted by position in the repaired update, all the other pages keep their relative
+    // order. Applying them in turn, each page already follows the page it is moved after.
+    pub moves: Vec<Move<P>>,
+}
+
+/// Fix the order of an update moving as few pages as possible.
+///
+/// Two pages can stay in place only if neither one must come before the other (even through other
+/// pages of the update) in the opposite order. "Must come before" is a partial order, so the
+/// largest set of pages that can stay is a maximum antichain of the violated pairs, found with
+/// Dilworth's theorem: it has `n - m` pages, where `m` is a maximum matching in the bipartite
+/// graph of the violated pairs.
+pub fn repair_update<P: Page>(
+    file: &InputFile<P>,
+    update: &[P],
+) -> Result<Repair<P>, CycleError<P>> {
+    let n = update.len();
+    let mut successors = update_graph(file, update);
+    if let Err(cycle) = topological_sort(&successors) {
+        return Err(CycleError {
+            pages: cycle.iter().map(|&i| update[i].clone()).collect(),
+        });
+    }
+
+    // violated[i] lists the positions j > i of the pages that must come before the page at i
+    let violated: Vec<Vec<usize>> = (0..n)
+        .map(|i| {
+            let reachable = reachable_from(&successors, i);
+            (0..i).filter(|&j| reachable[j]).collect()
+        })
+        .collect();
+    let violated = transpose(&violated);
+
+    let kept = maximum_antichain(&violated);
+
+    // Kept pages must stay in their original order
+    for pair in kept.windows(2) {
+        successors[pair[0]].push(pair[1]);
+    }
+    let (order, _) = topological_sort(&successors).expect("Kept pages must respect the rules");
+
+    let mut moves = vec![];
+    for (to, &from) in order.iter().enumerate() {
+        if kept.binary_search(&from).is_err() {
+            moves.push(Move {
+                page: update[from].clone(),
+                from,
+                to,
+                after: to.checked_sub(1).map(|x| update[order[x]].clone()),
+            });
+        }
+    }
+
+    let pages = order.iter().map(|&i| update[i].clone()).collect();
+    Ok(Repair { pages, moves })
+}
+
+/// Nodes reachable from `start` following one or more edges
+fn reachable_from(successors: &[Vec<usize>], start: usize) -> Vec<bool> {
+    let mut visited = vec![false; successors.len()];
+    let mut queue = VecDeque::from([start]);
+    while let Some(current) = queue.pop_front() {
+        for &next in &successors[current] {
+            if !visited[next] {
+                visited[next] = true;
+                queue.push_back(next);
+            }
+        }
+    }
+    visited
+}
+
+/// Reverse all the edges of a graph
+fn transpose(edges: &[Vec<usize>]) -> Vec<Vec<usize>> {
+    let mut transposed = vec![vec![]; edges.len()];
+    for (i, targets) in edges.iter().enumerate() {
+        for &j in targets {
+            transposed[j].push(i);
+        }
+    }
+    transposed
+}
+
+/// Largest set of nodes with no edge between them, sorted, for a transitively closed DAG.
+/// Uses König's theorem on the bipartite graph (left copy -> right copy) of the edges.
+fn maximum_antichain(edges: &[Vec<usize>]) -> Vec<usize> {
+    let n = edges.len();
+
+    // Kuhn's algorithm, matched_left[i] is the right node matched with the left node i
+    let mut matched_left: Vec<Option<usize>> = vec![None; n];
+    let mut matched_right: Vec<Option<usize>> = vec![None; n];
+    for i in 0..n {
+        let mut visited = vec![false; n];
+        augment(
+            edges,
+            i,
+            &mut visited,
+            &mut matched_left,
+            &mut matched_right,
+        );
+    }
+
+    // Alternating paths from the unmatched left nodes
+    let mut visited_left = vec![false; n];
+    let mut visited_right = vec![false; n];
+    let mut queue: VecDeque<usize> = (0..n).filter(|&i| matched_left[i].is_none()).collect();
+    for &i in &queue {
+        visited_left[i] = true;
+    }
+    while let Some(i) = queue.pop_front() {
+        for &j in &edges[i] {
+            if !visited_right[j] {
+                visited_right[j] = true;
+                if let Some(k) = matched_right[j] {
+                    if !visited_left[k] {
+                        visited_left[k] = true;
+                        queue.push_back(k);
+                    }
+                }
+            }
+        }
+    }
+
+    // The minimum vertex cover is the unvisited left nodes and the visited right nodes,
+    // the antichain is made of the nodes that have neither copy in the cover
+    (0..n)
+        .filter(|&i| visited_left[i] && !visited_right[i])
+        .collect()
+}
+
+fn augment(
+    edges: &[Vec<usize>],
+    i: usize,
+    visited: &mut [bool],
+    matched_left: &mut [Option<usize>],
+    matched_right: &mut [Option<usize>],
+) -> bool {
+    for &j in &edges[i] {
+        if visited[j] {
+            continue;
+        }
+        visited[j] = true;
+        let free = match matched_right[j] {
+            None => true,
+            Some(k) => augment(edges, k, visited, matched_left, matched_right),
+        };
+        if free {
+            matched_left[i] = Some(j);
+            matched_right[j] = Some(i);
+            return true;
+        }
+    }
+    false
+}
@@ -0,0 +1,211 @@
+use std::cell::OnceCell;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt::{Debug, Display};
+use std::hash::Hash;
+
+/// Identifier of a page; the puzzle uses numbers but any hashable and orderable value works
+pub trait Page: Clone + Eq + Hash + Ord + Debug + Display {}
+
+impl<T: Clone + Eq + Hash + Ord + Debug + Display> Page for T {}
+
+/// Ordering rules `X|Y` (page X must come before page Y), indexed by page
+#[derive(Clone, Debug)]
+pub struct Rules<P = i32> {
+    successors: HashMap<P, HashSet<P>>,
+    predecessors: HashMap<P, HashSet<P>>,
+    // Rules in insertion order, removed ones are left as holes
+    rules: Vec<Option<(P, P)>>,
+    // Position of each rule in `rules`
+    positions: HashMap<(P, P), usize>,
+    // Whether no page must come before itself, computed on demand and reset by any change
+    acyclic: OnceCell<bool>,
+}
+
+impl<P> Default for Rules<P> {
+    fn default() -> Self {
+        Rules {
+            successors: HashMap::new(),
+            predecessors: HashMap::new(),
+            rules: vec![],
+            positions: HashMap::new(),
+            acyclic: OnceCell::new(),
+        }
+    }
+}
+
+impl<P: Page> PartialEq for Rules<P> {
+    /// Rule sets are equal when they hold the same rules, whatever the insertion order
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().all(|(x, y)| other.contains(x, y))
+    }
+}
+
+impl<P: Page> Rules<P> {
+    /// Add the rule `before|after`, returns false if it was already present
+    pub fn insert(&mut self, before: P, after: P) -> bool {
+        let successors = self.successors.entry(before.clone()).or_default();
+        if !successors.insert(after.clone()) {
+            return false;
+        }
+        let predecessors = self.predecessors.entry(after.clone()).or_default();
+        predecessors.insert(before.clone());
+        self.positions
+            .insert((before.clone(), after.clone()), self.rules.len());
+        self.rules.push(Some((before, after)));
+        self.acyclic.take();
+        true
+    }
+
+    pub fn contains(&self, before: &P, after: &P) -> bool {
+        self.successors
+            .get(before)
+            .is_some_and(|x| x.contains(after))
+    }
+
+    /// Number of distinct rules
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    /// Pages that must come after `page`
+    pub fn successors<'a>(&'a self, page: &P) -> impl Iterator<Item = &'a P> + 'a {
+        self.successors.get(page).into_iter().flatten()
+    }
+
+    /// Number of pages that must come before `page`
+    pub fn num_predecessors(&self, page: &P) -> usize {
+        self.predecessors.get(page).map_or(0, HashSet::len)
+    }
+
+    /// Pages that must come before `page`
+    pub fn predecessors<'a>(&'a self, page: &P) -> impl Iterator<Item = &'a P> + 'a {
+        self.predecessors.get(page).into_iter().flatten()
+    }
+
+    /// All the rules as `(before, after)` pairs, in insertion order
+    pub fn iter(&self) -> impl Iterator<Item = &(P, P)> + '_ {
+        self.rules.iter().flatten()
+    }
+
+    /// Whether the rules can all be followed at once, i.e. no page must come before itself
+    pub fn is_acyclic(&self) -> bool {
+        *self.acyclic.get_or_init(|| {
+            // Kahn's algorithm: remove pages without predecessors until none are left
+            let mut in_degree: HashMap<&P, usize> =
+                self.successors.keys().map(|x| (x, 0)).collect();
+            for (page, predecessors) in &self.predecessors {
+                in_degree.insert(page, predecessors.len());
+            }
+            let mut available: Vec<&P> = in_degree
+                .iter()
+                .filter(|(_, &degree)| degree == 0)
+                .map(|(&x, _)| x)
+                .collect();
+            let mut removed = 0;
+            while let Some(page) = available.pop() {
+                removed += 1;
+                for next in self.successors(page) {
+                    let degree = in_degree.get_mut(next).unwrap();
+                    *degree -= 1;
+                    if *degree == 0 {
+                        available.push(next);
+                    }
+                }
+            }
+            removed == in_degree.len()
+        })
+    }
+
+    /// Whether `before` must come before `after`, directly or through a chain of rules
+    pub fn must_precede(&self, before: &P, after: &P) -> bool {
+        self.reachable_from(before, None).contains(after)
+    }
+
+    /// Keep only the rules between the given pages
+    pub fn restrict(&self, pages: &[P]) -> Rules<P> {
+        let pages: HashSet<&P> = pages.iter().collect();
+        self.iter()
+            .filter(|(before, after)| pages.contains(before) && pages.contains(after))
+            .cloned()
+            .collect()
+    }
+
+    /// All the rules implied transitively by this rule set.
+    /// Use `restrict(pages).closure()` to get the closure for a subset of pages.
+    pub fn closure(&self) -> Rules<P> {
+        let mut closure = Rules::default();
+        for before in self.successors.keys() {
+            for after in self.reachable_from(before, None) {
+                closure.insert(before.clone(), after.clone());
+            }
+        }
+        closure
+    }
+
+    /// Rules that are implied by the other rules, sorted.
+    /// Removing all of them at once is only safe if the rules are not cyclic, see `minimize`.
+    pub fn redundant_rules(&self) -> Vec<(P, P)> {
+        let mut redundant: Vec<_> = self
+            .iter()
+            .filter(|(before, after)| {
+                self.reachable_from(before, Some((before, after)))
+                    .contains(after)
+            })
+            .cloned()
+            .collect();
+        redundant.sort();
+        redundant
+    }
+
+    /// Remove redundant rules one at a time, so the closure is always preserved
+    pub fn minimize(&self) -> Rules<P> {
+        let mut rules: Vec<(P, P)> = self.iter().cloned().collect();
+        rules.sort();
+
+        let mut minimized = self.clone();
+        for (before, after) in rules {
+            if minimized
+                .reachable_from(&before, Some((&before, &after)))
+                .contains(&after)
+            {
+                minimized.remove(&before, &after);
+            }
+        }
+        minimized
+    }
+
+    fn remove(&mut self, before: &P, after: &P) {
+        if let Some(x) = self.successors.get_mut(before) {
+            if x.remove(after) {
+                self.predecessors.get_mut(after).unwrap().remove(before);
+                let position = self.positions.remove(&(before.clone(), after.clone()));
+                self.rules[position.unwrap()] = None;
+                self.acyclic.take();
+            }
+        }
+    }
+
+    /// Pages reachable from `page` following one or more rules, optionally ignoring one rule
+    fn reachable_from<'a>(&'a self, page: &'a P, ignored: Option<(&P, &P)>) -> HashSet<&'a P> {
+        let mut visited = HashSet::new();
+        let mut queue = VecDeque::from([page]);
+        while let Some(current) = queue.pop_front() {
+            for next in self.successors(current) {
+                if ignored != Some((current, next)) && visited.insert(next) {
+                    queue.push_back(next);
+                }
+            }
+        }
+        visited
+    }
+}
+
+impl<P: Page> FromIterator<(P, P)> for Rules<P> {
+    fn from_iter<T: IntoIterator<Item = (P, P)>>(iter: T) -> Self {
+        let mut rules = Rules::default();
+        for (before, after) in iter {
+            rules.insert(before, after);
+        }
+        rules
+    }
+}
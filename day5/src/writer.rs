@@ -0,0 +1,39 @@
+use crate::rules::Page;
+use crate::InputFile;
+use std::collections::HashSet;
+
+#[derive(Default, Clone, Copy, Debug)]
+pub struct WriteOptions {
+    // Sort the rules and the updates (pages within an update are never reordered)
+    pub sorted: bool,
+    // Drop repeated rules and updates
+    pub deduplicated: bool,
+}
+
+/// Serialize rules and updates in the same format read by `parse_file`
+pub fn write_file<P: Page>(input: &InputFile<P>, options: WriteOptions) -> String {
+    let mut rules: Vec<&(P, P)> = input.rules.iter().collect();
+    let mut updates: Vec<&Vec<P>> = input.updates.iter().collect();
+    if options.sorted {
+        rules.sort();
+        updates.sort();
+    }
+    if options.deduplicated {
+        let mut seen = HashSet::new();
+        rules.retain(|&x| seen.insert(x));
+        let mut seen = HashSet::new();
+        updates.retain(|&x| seen.insert(x));
+    }
+
+    let mut output = String::new();
+    for (before, after) in rules {
+        output += &format!("{}|{}\n", before, after);
+    }
+    output += "\n";
+    for update in updates {
+        let pages: Vec<String> = update.iter().map(|x| x.to_string()).collect();
+        output += &pages.join(",");
+        output += "\n";
+    }
+    output
+}
@@ -0,0 +1,54 @@
+use crate::obstacles::count_obstacle_locations_parallel;
+use crate::{count_obstacle_locations, Cell, State};
+use std::time::Instant;
+
+/// Compare the obstacle search against the original brute force on the given map
+pub fn run(state: &State, threads: usize) {
+    let start = Instant::now();
+    let naive = count_obstacle_locations_naive(state.clone());
+    let naive_time = start.elapsed();
+
+    let start = Instant::now();
+    let fast = count_obstacle_locations(state);
+    let fast_time = start.elapsed();
+
+    let start = Instant::now();
+    let parallel = count_obstacle_locations_parallel(state, threads);
+    let parallel_time = start.elapsed();
+
+    assert_eq!(naive, fast, "The implementations disagree");
+    assert_eq!(fast, parallel, "The implementations disagree");
+    println!("Obstacle locations: {}", fast);
+    println!("Brute force: {:?}", naive_time);
+    println!(
+        "Jump tables: {:?} ({:.1}x)",
+        fast_time,
+        naive_time.as_secs_f64() / fast_time.as_secs_f64()
+    );
+    println!(
+        "Jump tables on {} threads: {:?} ({:.1}x)",
+        threads,
+        parallel_time,
+        naive_time.as_secs_f64() / parallel_time.as_secs_f64()
+    );
+}
+
+/// The original search: try a block on every free cell and simulate the whole route each time.
+/// Portals cannot be blocked.
+pub fn count_obstacle_locations_naive(mut state: State) -> i32 {
+    let mut count = 0;
+    for row in 0..state.num_rows() {
+        for column in 0..state.num_columns() {
+            if state.cell_at((row, column)) == Cell::Free
+                && !state.portals.contains_key(&(row, column))
+            {
+                state.set_cell_at((row, column), Cell::Block);
+                if state.is_endless_loop() {
+                    count += 1;
+                }
+                state.set_cell_at((row, column), Cell::Free);
+            }
+        }
+    }
+    count
+}
@@ -0,0 +1,171 @@
+use crate::{Cell, Direction, State, StepResult};
+use std::collections::HashSet;
+
+/// Location, direction and turn policy phase of a guard that is still walking
+type Walking = ((i32, i32), Direction, usize);
+
+/// What happens when two guards meet
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum Meeting {
+    // A guard turns in front of another guard, as if it was a block
+    Block,
+    // Guards walk through each other
+    Pass,
+    // Guards that end up in the same cell, or walk past each other, stop there
+    Stop,
+}
+
+impl Meeting {
+    pub fn from_name(name: &str) -> Option<Meeting> {
+        match name {
+            "block" => Some(Meeting::Block),
+            "pass" => Some(Meeting::Pass),
+            "stop" => Some(Meeting::Stop),
+            _ => None,
+        }
+    }
+}
+
+/// How the walk of a guard ended, `steps` counts the steps of the whole group
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum GuardOutcome {
+    // Left the map at this step
+    Exited { steps: usize },
+    // Walking in circles, found at this step
+    Cycle { steps: usize },
+    // Met another guard in `location` at this step, or found itself on a block there
+    Stopped { steps: usize, location: (i32, i32) },
+}
+
+/// Move all the guards one step at a time, in lock-step, until each one has left the map, stopped
+/// or entered a cycle. Each state keeps the trail of its own guard.
+/// With `Pass` the guards do not affect each other, so each cycle is found on its own; otherwise a
+/// cycle is only certain when the whole group is back in a previous configuration.
+pub fn run_guards(guards: &mut [State], meeting: Meeting) -> Vec<GuardOutcome> {
+    let mut outcomes: Vec<Option<GuardOutcome>> = vec![None; guards.len()];
+    let mut seen = HashSet::new();
+    for steps in 1.. {
+        let active: Vec<usize> = (0..guards.len())
+            .filter(|&i| outcomes[i].is_none())
+            .collect();
+        if active.is_empty() {
+            break;
+        }
+        // Guards still on the map at the start of the step, including the stopped ones
+        let present: Vec<usize> = (0..guards.len())
+            .filter(|&i| !matches!(outcomes[i], Some(GuardOutcome::Exited { .. })))
+            .collect();
+        let previous: Vec<(i32, i32)> = guards.iter().map(|x| x.guard_location).collect();
+        let targets: Vec<Option<(i32, i32)>> = guards.iter().map(target).collect();
+
+        for &i in &active {
+            let state = &mut guards[i];
+            let result = match (
+                meeting,
+                state.advance(state.guard_location, state.guard_direction),
+            ) {
+                (Meeting::Block, Some(next))
+                    if targets[i].is_some_and(|target| {
+                        present.iter().any(|&j| {
+                            j != i
+                                && (previous[j] == target
+                                    || (outcomes[j].is_none() && targets[j] == Some(target)))
+                        })
+                    }) =>
+                {
+                    // Turn in front of the other guard, or of the cell both want to enter
+                    let cell = state.cell_at(next);
+                    state.set_cell_at(next, Cell::Block);
+                    let result = state.step();
+                    state.set_cell_at(next, cell);
+                    result
+                }
+                _ => state.step(),
+            };
+            match result {
+                StepResult::Ok => {}
+                StepResult::ReachedExit => outcomes[i] = Some(GuardOutcome::Exited { steps }),
+                // The other guards may still change the route of this one
+                StepResult::Cycle if meeting != Meeting::Pass => {}
+                StepResult::Cycle => outcomes[i] = Some(GuardOutcome::Cycle { steps }),
+                StepResult::OnBlock => {
+                    outcomes[i] = Some(GuardOutcome::Stopped {
+                        steps,
+                        location: state.guard_location,
+                    })
+                }
+            }
+        }
+
+        if meeting == Meeting::Stop {
+            let met: Vec<usize> = active
+                .iter()
+                .copied()
+                .filter(|&i| outcomes[i].is_none())
+                .filter(|&i| {
+                    present.iter().any(|&j| {
+                        j != i
+                            && !matches!(outcomes[j], Some(GuardOutcome::Exited { .. }))
+                            && (guards[j].guard_location == guards[i].guard_location
+                                || (guards[j].guard_location == previous[i]
+                                    && guards[i].guard_location == previous[j]))
+                    })
+                })
+                .collect();
+            for i in met {
+                outcomes[i] = Some(GuardOutcome::Stopped {
+                    steps,
+                    location: guards[i].guard_location,
+                });
+            }
+        }
+
+        if meeting != Meeting::Pass {
+            // Stopped and exited guards never move again, so the active ones are enough
+            let group: Vec<Option<Walking>> = guards
+                .iter()
+                .zip(&outcomes)
+                .map(|(x, outcome)| {
+                    outcome.is_none().then(|| {
+                        (
+                            x.guard_location,
+                            x.guard_direction,
+                            x.turns % x.turn_policy.phases(),
+                        )
+                    })
+                })
+                .collect();
+            if !seen.insert(group) {
+                for outcome in outcomes.iter_mut().filter(|x| x.is_none()) {
+                    *outcome = Some(GuardOutcome::Cycle { steps });
+                }
+            }
+        }
+    }
+    outcomes.into_iter().map(Option::unwrap).collect()
+}
+
+/// Where the guard would be after its next step, if it moves
+fn target(state: &State) -> Option<(i32, i32)> {
+    let next = state.advance(state.guard_location, state.guard_direction)?;
+    (state.cell_at(next) != Cell::Block).then(|| state.teleport(next))
+}
+
+/// Number of cells walked by at least one guard
+pub fn combined_coverage(guards: &[State]) -> i32 {
+    let Some(first) = guards.first() else {
+        return 0;
+    };
+    let mut count = 0;
+    for row in 0..first.num_rows() {
+        for column in 0..first.num_columns() {
+            if guards
+                .iter()
+                .any(|x| matches!(x.cell_at((row, column)), Cell::Walked(_)))
+            {
+                count += 1;
+            }
+        }
+    }
+    count
+}
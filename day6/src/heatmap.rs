@@ -0,0 +1,103 @@
+use crate::{Cell, Direction, DirectionField, State, StepResult};
+use std::io::Write;
+
+/// How often the guard entered each cell, and in which directions it walked there
+pub struct HeatMap {
+    num_columns: usize,
+    cells: Vec<Cell>,
+    visits: Vec<u32>,
+}
+
+impl HeatMap {
+    /// Walk the guard until it leaves the map or is found in a loop, counting the visits.
+    /// The starting cell counts as visited once.
+    pub fn new(state: &State) -> HeatMap {
+        let mut state = state.clone();
+        let num_columns = state.num_columns() as usize;
+        let index = |location: (i32, i32)| location.0 as usize * num_columns + location.1 as usize;
+        let mut visits = vec![0; state.map.len() * num_columns];
+        visits[index(state.guard_location)] += 1;
+        loop {
+            let moves = state.moves;
+            let result = state.step();
+            if state.moves > moves {
+                visits[index(state.guard_location)] += 1;
+            }
+            if !matches!(result, StepResult::Ok) {
+                break;
+            }
+        }
+        HeatMap {
+            num_columns,
+            cells: state.map.into_iter().flatten().collect(),
+            visits,
+        }
+    }
+
+    fn directions(&self, index: usize) -> DirectionField {
+        match self.cells[index] {
+            Cell::Walked(directions) => directions,
+            _ => DirectionField::None,
+        }
+    }
+
+    /// One line per walked cell: `row,column,visits,directions`, with the directions written
+    /// with the same symbols as the guard
+    pub fn write_csv(&self, out: &mut impl Write) -> std::io::Result<()> {
+        writeln!(out, "row,column,visits,directions")?;
+        for (i, visits) in self.visits.iter().enumerate() {
+            if !matches!(self.cells[i], Cell::Walked(_)) {
+                continue;
+            }
+            let directions: String = Direction::ALL
+                .iter()
+                .filter(|x| self.directions(i).contains(x.to_field()))
+                .map(|x| x.to_char())
+                .collect();
+            writeln!(
+                out,
+                "{},{},{},{}",
+                i / self.num_columns,
+                i % self.num_columns,
+                visits,
+                directions
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Binary PPM image with `scale` pixels per cell: blocks are dark grey, free cells white and
+    /// walked cells go from blue to red as the number of visits grows
+    pub fn write_ppm(&self, out: &mut impl Write, scale: usize) -> std::io::Result<()> {
+        assert!(scale > 0);
+        let num_rows = self.cells.len() / self.num_columns;
+        let max_visits = self.visits.iter().copied().max().unwrap_or(0).max(1);
+        writeln!(
+            out,
+            "P6\n{} {}\n255",
+            self.num_columns * scale,
+            num_rows * scale
+        )?;
+        for row in 0..num_rows {
+            let mut line = Vec::with_capacity(self.num_columns * scale * 3);
+            for column in 0..self.num_columns {
+                let i = row * self.num_columns + column;
+                let color = match self.cells[i] {
+                    Cell::Block => [64, 64, 64],
+                    Cell::Free => [255, 255, 255],
+                    Cell::Walked(_) => {
+                        let heat = (self.visits[i] * 255 / max_visits) as u8;
+                        [heat, 0, 255 - heat]
+                    }
+                };
+                for _ in 0..scale {
+                    line.extend_from_slice(&color);
+                }
+            }
+            for _ in 0..scale {
+                out.write_all(&line)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,11 +1,30 @@
+mod bench;
+mod guards;
+mod heatmap;
+mod obstacles;
+mod placement;
+mod render;
+
 use bitflags::bitflags;
+use guards::{combined_coverage, run_guards, Meeting};
+use obstacles::{
+    count_obstacle_locations, count_obstacle_locations_parallel, find_obstacle_locations,
+    render_obstacles,
+};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::{Display, Formatter};
 
-#[derive(PartialEq, Debug, Copy, Clone)]
+/// Heading of the guard, listed clockwise
+#[derive(PartialEq, Eq, Hash, Debug, Copy, Clone)]
 enum Direction {
     Up,
+    UpRight,
     Right,
+    DownRight,
     Down,
+    DownLeft,
     Left,
+    UpLeft,
 }
 
 #[derive(PartialEq, Debug, Copy, Clone)]
@@ -26,27 +45,157 @@ bitflags! {
         const Right = 0x2;
         const Down = 0x4;
         const Left = 0x8;
+        const UpRight = 0x10;
+        const DownRight = 0x20;
+        const DownLeft = 0x40;
+        const UpLeft = 0x80;
     }
 }
 
 impl Direction {
+    const ALL: [Direction; 8] = [
+        Direction::Up,
+        Direction::UpRight,
+        Direction::Right,
+        Direction::DownRight,
+        Direction::Down,
+        Direction::DownLeft,
+        Direction::Left,
+        Direction::UpLeft,
+    ];
+
+    /// Turn clockwise by `eighths` of a full turn
+    fn turn_clockwise(self, eighths: usize) -> Direction {
+        Direction::ALL[(self.index() + eighths) % Direction::ALL.len()]
+    }
+
     fn rotate(&self) -> Direction {
-        match self {
-            Direction::Up => Direction::Right,
-            Direction::Right => Direction::Down,
-            Direction::Down => Direction::Left,
-            Direction::Left => Direction::Up,
-        }
+        self.turn_clockwise(2)
+    }
+
+    fn rotate_left(&self) -> Direction {
+        self.turn_clockwise(6)
     }
 
-    fn to_field(&self) -> DirectionField {
+    fn to_field(self) -> DirectionField {
         match self {
             Direction::Up => DirectionField::Up,
+            Direction::UpRight => DirectionField::UpRight,
             Direction::Right => DirectionField::Right,
+            Direction::DownRight => DirectionField::DownRight,
             Direction::Down => DirectionField::Down,
+            Direction::DownLeft => DirectionField::DownLeft,
             Direction::Left => DirectionField::Left,
+            Direction::UpLeft => DirectionField::UpLeft,
+        }
+    }
+
+    fn from_char(c: char) -> Option<Direction> {
+        Direction::ALL.into_iter().find(|x| x.to_char() == c)
+    }
+
+    /// The guard glyph used in the puzzle maps.
+    /// There is none for diagonals, so they use the digits of a numeric keypad.
+    fn to_char(self) -> char {
+        match self {
+            Direction::Up => '^',
+            Direction::UpRight => '9',
+            Direction::Right => '>',
+            Direction::DownRight => '3',
+            Direction::Down => 'v',
+            Direction::DownLeft => '1',
+            Direction::Left => '<',
+            Direction::UpLeft => '7',
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+
+    /// Row and column offset of a single step
+    fn delta(self) -> (i32, i32) {
+        match self {
+            Direction::Up => (-1, 0),
+            Direction::UpRight => (-1, 1),
+            Direction::Right => (0, 1),
+            Direction::DownRight => (1, 1),
+            Direction::Down => (1, 0),
+            Direction::DownLeft => (1, -1),
+            Direction::Left => (0, -1),
+            Direction::UpLeft => (-1, -1),
+        }
+    }
+}
+
+/// How the guard changes direction when facing a block
+#[derive(PartialEq, Debug, Copy, Clone)]
+enum TurnPolicy {
+    // Turn right 90 degrees (the puzzle rules)
+    Right,
+    // Turn left 90 degrees
+    Left,
+    // Walk back where it came from
+    Reverse,
+    // Turn right, then left the next time, and so on
+    Alternate,
+    // Turn right 45 degrees, the guard can walk diagonally
+    Right45,
+    // Turn left 45 degrees, the guard can walk diagonally
+    Left45,
+}
+
+impl TurnPolicy {
+    /// The new direction, given how many turns the guard already took
+    fn turn(self, direction: Direction, turns: usize) -> Direction {
+        match self {
+            TurnPolicy::Right => direction.rotate(),
+            TurnPolicy::Left => direction.rotate_left(),
+            TurnPolicy::Reverse => direction.rotate().rotate(),
+            TurnPolicy::Alternate if turns.is_multiple_of(2) => direction.rotate(),
+            TurnPolicy::Alternate => direction.rotate_left(),
+            TurnPolicy::Right45 => direction.turn_clockwise(1),
+            TurnPolicy::Left45 => direction.turn_clockwise(7),
+        }
+    }
+
+    /// After how many turns the policy repeats itself.
+    /// The guard is in a loop only if it is back in the same place, direction and phase.
+    fn phases(self) -> usize {
+        match self {
+            TurnPolicy::Alternate => 2,
+            _ => 1,
         }
     }
+
+    fn from_name(name: &str) -> Option<TurnPolicy> {
+        match name {
+            "right" => Some(TurnPolicy::Right),
+            "left" => Some(TurnPolicy::Left),
+            "reverse" => Some(TurnPolicy::Reverse),
+            "alternate" => Some(TurnPolicy::Alternate),
+            "right45" => Some(TurnPolicy::Right45),
+            "left45" => Some(TurnPolicy::Left45),
+            _ => None,
+        }
+    }
+}
+
+/// What happens when the guard walks past the edge of the map
+#[derive(PartialEq, Debug, Copy, Clone)]
+enum Boundary {
+    // The guard leaves, as in the puzzle
+    Exit,
+    // The guard comes back from the opposite edge
+    Wrap,
+}
+
+/// One end of a pair of portals: walking into it moves the guard to the other end,
+/// keeping the same direction
+#[derive(PartialEq, Debug, Copy, Clone)]
+struct Portal {
+    name: char,
+    target: (i32, i32),
 }
 
 #[derive(Clone)]
@@ -54,12 +203,63 @@ struct State {
     map: Vec<Vec<Cell>>,
     guard_location: (i32, i32),
     guard_direction: Direction,
+    turn_policy: TurnPolicy,
+    boundary: Boundary,
+    portals: HashMap<(i32, i32), Portal>,
+    // Number of moves to another cell so far, going through a portal counts as one
+    moves: usize,
+    // Number of turns taken so far
+    turns: usize,
+    // Guard states already reached, only needed by policies with more than one phase:
+    // otherwise the walked directions of each cell are enough
+    seen: HashSet<((i32, i32), Direction, usize)>,
 }
 
 enum StepResult {
     Ok,
     Cycle,
     ReachedExit,
+    // The guard stands on a block, so it cannot move
+    OnBlock,
+}
+
+/// How a whole walk ended
+#[derive(PartialEq, Debug)]
+enum RunResult {
+    // The guard left the map from `exit` after this many moves and turns
+    Exited {
+        moves: usize,
+        turns: usize,
+        exit: (i32, i32),
+    },
+    // The guard is back in `start` every `period` steps
+    Cycle {
+        start: ((i32, i32), Direction),
+        period: usize,
+    },
+    OnBlock {
+        location: (i32, i32),
+    },
+}
+
+impl Display for RunResult {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RunResult::Exited { moves, turns, exit } => write!(
+                f,
+                "the guard leaves the map from {:?} after {} moves and {} turns",
+                exit, moves, turns
+            ),
+            RunResult::Cycle { start, period } => write!(
+                f,
+                "the guard walks in a loop of {} steps entered at {:?} going {:?}",
+                period, start.0, start.1
+            ),
+            RunResult::OnBlock { location } => {
+                write!(f, "the guard is stuck on the block at {:?}", location)
+            }
+        }
+    }
 }
 
 impl State {
@@ -80,63 +280,59 @@ impl State {
     }
 
     fn advance(&self, input: (i32, i32), direction: Direction) -> Option<(i32, i32)> {
-        let (row, column) = input;
-        match direction {
-            Direction::Up => {
-                if row > 0 {
-                    Some((row - 1, column))
-                } else {
-                    None
-                }
-            }
-            Direction::Right => {
-                if column < self.num_columns() - 1 {
-                    Some((row, column + 1))
-                } else {
-                    None
-                }
-            }
-            Direction::Down => {
-                if row < self.num_rows() - 1 {
-                    Some((row + 1, column))
-                } else {
-                    None
-                }
-            }
-            Direction::Left => {
-                if column > 0 {
-                    Some((row, column - 1))
-                } else {
-                    None
-                }
-            }
+        let (row_delta, column_delta) = direction.delta();
+        let (row, column) = (input.0 + row_delta, input.1 + column_delta);
+        match self.boundary {
+            Boundary::Exit => ((0..self.num_rows()).contains(&row)
+                && (0..self.num_columns()).contains(&column))
+            .then_some((row, column)),
+            Boundary::Wrap => Some((
+                row.rem_euclid(self.num_rows()),
+                column.rem_euclid(self.num_columns()),
+            )),
         }
     }
 
+    /// Where the guard ends up after walking into `location`
+    fn teleport(&self, location: (i32, i32)) -> (i32, i32) {
+        self.portals.get(&location).map_or(location, |x| x.target)
+    }
+
     fn step(&mut self) -> StepResult {
         if let Some(next_position) = self.advance(self.guard_location, self.guard_direction) {
             // Update the guard location
             match self.cell_at(next_position) {
-                Cell::Free | Cell::Walked(_) => self.guard_location = next_position,
-                Cell::Block => self.guard_direction = self.guard_direction.rotate(),
-            }
-            // Mark as reached and detect a possible cycle
-            if self.mark_reached() {
-                StepResult::Cycle
-            } else {
-                StepResult::Ok
+                Cell::Free | Cell::Walked(_) => {
+                    if self.portals.contains_key(&next_position) {
+                        // The guard only passes through the entrance, so no direction is
+                        // recorded there: the cycle check is done on the other end
+                        if self.cell_at(next_position) == Cell::Free {
+                            self.set_cell_at(next_position, Cell::Walked(DirectionField::None));
+                        }
+                    }
+                    self.guard_location = self.teleport(next_position);
+                    self.moves += 1;
+                }
+                Cell::Block => {
+                    self.guard_direction = self.turn_policy.turn(self.guard_direction, self.turns);
+                    self.turns += 1;
+                }
             }
+            // Mark as reached and detect a possible cycle.
+            // A turn changes the state of the guard like a move does: it is recorded so that
+            // coming back to the same cell and direction later, in any way, is a cycle.
+            self.mark_reached()
         } else {
             StepResult::ReachedExit
         }
     }
 
     // Marks the cell as reached.
-    // If a cycle is detected, true is returned
-    fn mark_reached(&mut self) -> bool {
+    // If a cycle is detected, `StepResult::Cycle` is returned
+    fn mark_reached(&mut self) -> StepResult {
         let location = self.guard_location;
         let direction = self.guard_direction;
-        match self.cell_at(location) {
+        let walked = match self.cell_at(location) {
             Cell::Free => {
                 self.set_cell_at(location, Cell::Walked(self.guard_direction.to_field()));
                 false
@@ -150,29 +346,73 @@ impl State {
                     false
                 }
             }
-            Cell::Block => {
-                panic!("The guard should not be on a block");
-            }
+            Cell::Block => return StepResult::OnBlock,
+        };
+
+        let phases = self.turn_policy.phases();
+        let cycle = if phases == 1 {
+            walked
+        } else {
+            !self.seen.insert((location, direction, self.turns % phases))
+        };
+        if cycle {
+            StepResult::Cycle
+        } else {
+            StepResult::Ok
         }
     }
 
-    fn run(&mut self) {
+    /// Walk until the guard leaves the map or is found walking in circles
+    fn run(&mut self) -> RunResult {
+        let (moves, turns) = (self.moves, self.turns);
         loop {
             match self.step() {
                 StepResult::Ok => {}
-                StepResult::ReachedExit => break,
-                StepResult::Cycle => panic!("Cyclic maze"),
+                StepResult::ReachedExit => {
+                    return RunResult::Exited {
+                        moves: self.moves - moves,
+                        turns: self.turns - turns,
+                        exit: self.guard_location,
+                    }
+                }
+                // The first state reached twice is where the loop starts
+                StepResult::Cycle => {
+                    return RunResult::Cycle {
+                        start: (self.guard_location, self.guard_direction),
+                        period: self.cycle_period(),
+                    }
+                }
+                StepResult::OnBlock => {
+                    return RunResult::OnBlock {
+                        location: self.guard_location,
+                    }
+                }
             }
         }
     }
 
+    /// Number of steps to come back to the current state, for a guard that is in a loop
+    fn cycle_period(&self) -> usize {
+        // Walk again on a map without trails, starting from here
+        let mut copy = self.clone();
+        for cell in copy.map.iter_mut().flatten() {
+            if let Cell::Walked(_) = cell {
+                *cell = Cell::Free;
+            }
+        }
+        copy.seen.clear();
+        copy.mark_reached();
+        while let StepResult::Ok = copy.step() {}
+        copy.moves - self.moves + copy.turns - self.turns
+    }
+
     fn is_endless_loop(&self) -> bool {
         // work on a local copy
         let mut copy = self.clone();
         loop {
             match copy.step() {
                 StepResult::Ok => {}
-                StepResult::ReachedExit => return false,
+                StepResult::ReachedExit | StepResult::OnBlock => return false,
                 StepResult::Cycle => return true,
             }
         }
@@ -188,9 +428,152 @@ impl State {
 }
 
 fn main() {
-    let state = parse_file(include_str!("input.txt"));
-    println!("Part 1: {}", count_walkable_cells(state.clone()));
-    println!("Part 2: {}", count_obstacle_locations(state));
+    // Use all the cores unless limited with `--threads N`
+    let mut threads = std::thread::available_parallelism().map_or(1, |x| x.get());
+    // Replay and export options
+    let mut every = 1;
+    let mut scale = 4;
+    // Number of new blocks for `trap` and `spread`
+    let mut blocks = 2;
+    let mut output = None;
+    // Simulation options
+    let mut turn_policy = TurnPolicy::Right;
+    let mut boundary = Boundary::Exit;
+    let mut meeting = Meeting::Block;
+    let mut map_path = None;
+    let mut command = None;
+    let mut args = std::env::args().skip(1);
+    let positive = |name: &str, value: Option<String>| {
+        value
+            .and_then(|x| x.parse().ok())
+            .filter(|&x| x > 0)
+            .unwrap_or_else(|| panic!("Expected a positive number after {}", name))
+    };
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--threads" => threads = positive(&arg, args.next()),
+            "--every" => every = positive(&arg, args.next()),
+            "--scale" => scale = positive(&arg, args.next()),
+            "--blocks" => blocks = positive(&arg, args.next()),
+            "--output" => output = Some(args.next().expect("Expected a file name")),
+            "--map" => map_path = Some(args.next().expect("Expected a file name")),
+            "--wrap" => boundary = Boundary::Wrap,
+            "--turn" => {
+                turn_policy = args.next().and_then(|x| TurnPolicy::from_name(&x)).expect(
+                    "Expected a turn policy: right, left, reverse, alternate, right45 or left45",
+                )
+            }
+            "--meet" => {
+                meeting = args
+                    .next()
+                    .and_then(|x| Meeting::from_name(&x))
+                    .expect("Expected a meeting rule: block, pass or stop")
+            }
+            _ if command.is_none() => command = Some(arg),
+            _ => panic!("Unexpected argument '{}'", arg),
+        }
+    }
+
+    let map = match map_path {
+        Some(path) => std::fs::read_to_string(path).expect("Cannot read the map"),
+        None => include_str!("input.txt").to_string(),
+    };
+    // Only the `guards` command accepts more than one guard
+    let parsed = if command.as_deref() == Some("guards") {
+        parse_guards(&map)
+    } else {
+        parse_file(&map).map(|x| vec![x])
+    };
+    let mut guards = match parsed {
+        Ok(guards) => guards,
+        Err(err) => {
+            eprintln!("Invalid map: {}", err);
+            std::process::exit(1);
+        }
+    };
+    for guard in &mut guards {
+        guard.turn_policy = turn_policy;
+        guard.boundary = boundary;
+    }
+    let state = guards[0].clone();
+
+    match command.as_deref() {
+        None => {
+            println!("Part 1: {}", count_walkable_cells(state.clone()));
+            println!(
+                "Part 2: {}",
+                count_obstacle_locations_parallel(&state, threads)
+            );
+        }
+        Some("walk") => {
+            let mut state = state;
+            let result = state.run();
+            println!("{} cells walked: {}", state.count_walked(), result);
+        }
+        Some("obstacles") => {
+            let obstacles = find_obstacle_locations(&state, threads);
+            print!("{}", render_obstacles(&state, &obstacles));
+            for x in &obstacles {
+                println!(
+                    "Obstacle at {:?}: loop of {} steps entered at {:?} going {:?}",
+                    x.location, x.period, x.entry.0, x.entry.1
+                );
+            }
+        }
+        Some("replay") => {
+            let mut state = state;
+            let result = match output {
+                Some(path) => {
+                    let file = std::fs::File::create(path).expect("Cannot create the output");
+                    render::replay(&mut state, &mut std::io::BufWriter::new(file), every)
+                }
+                None => render::replay(&mut state, &mut std::io::stdout().lock(), every),
+            };
+            result.expect("Cannot write the replay");
+        }
+        Some("trap") => match placement::minimum_trap(&state, blocks) {
+            Some(placed) => println!("The guard is trapped by new blocks at {:?}", placed),
+            None => println!("Cannot trap the guard with {} blocks or less", blocks),
+        },
+        Some("spread") => {
+            let (placed, walked) = placement::maximize_walked(&state, blocks);
+            println!("{} cells walked with blocks at {:?}", walked, placed);
+        }
+        Some("heatmap") => {
+            let heat_map = heatmap::HeatMap::new(&state);
+            let result = match output {
+                Some(path) => {
+                    let file = std::fs::File::create(&path).expect("Cannot create the output");
+                    let mut file = std::io::BufWriter::new(file);
+                    if path.ends_with(".ppm") {
+                        heat_map.write_ppm(&mut file, scale)
+                    } else {
+                        heat_map.write_csv(&mut file)
+                    }
+                }
+                None => heat_map.write_csv(&mut std::io::stdout().lock()),
+            };
+            result.expect("Cannot write the heat map");
+        }
+        Some("guards") => {
+            let starts: Vec<_> = guards.iter().map(|x| x.guard_location).collect();
+            let outcomes = run_guards(&mut guards, meeting);
+            for ((guard, start), outcome) in guards.iter().zip(starts).zip(outcomes) {
+                println!(
+                    "Guard from {:?}: {:?}, {} cells walked",
+                    start,
+                    outcome,
+                    guard.count_walked()
+                );
+            }
+            println!("Cells walked by any guard: {}", combined_coverage(&guards));
+        }
+        Some("bench") => bench::run(&state, threads),
+        Some(arg) => panic!(
+            "Invalid argument '{}', expected: [walk|obstacles|trap|spread|replay|heatmap|guards|bench] [--map FILE] [--threads N] [--every N] [--scale N] [--blocks N] [--output FILE] [--turn POLICY] [--wrap] [--meet RULE]",
+            arg
+        ),
+    }
 }
 
 fn count_walkable_cells(mut state: State) -> i32 {
@@ -198,46 +581,168 @@ fn count_walkable_cells(mut state: State) -> i32 {
     state.count_walked()
 }
 
-fn count_obstacle_locations(mut state: State) -> i32 {
-    let mut count = 0;
-    for row in 0..state.num_rows() {
-        for column in 0..state.num_columns() {
-            if state.cell_at((row, column)) == Cell::Free {
-                state.set_cell_at((row, column), Cell::Block);
-                if state.is_endless_loop() {
-                    count += 1;
-                }
-                state.set_cell_at((row, column), Cell::Free);
+/// Errors found while parsing a map, rows and columns start from 0 like the map coordinates
+#[derive(PartialEq, Debug)]
+enum ParseError {
+    EmptyMap,
+    InvalidCell {
+        row: usize,
+        column: usize,
+        found: char,
+    },
+    // All the rows must have the same length as the first one
+    NonRectangular {
+        row: usize,
+        expected: usize,
+        found: usize,
+    },
+    NoGuard,
+    MultipleGuards {
+        first: (i32, i32),
+        second: (i32, i32),
+    },
+    // Portals are letters from 'A' to 'Z', each used by exactly two cells
+    UnpairedPortal {
+        name: char,
+        count: usize,
+    },
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::EmptyMap => write!(f, "the map is empty"),
+            ParseError::InvalidCell { row, column, found } => {
+                write!(
+                    f,
+                    "row {}, column {}: invalid cell '{}'",
+                    row, column, found
+                )
+            }
+            ParseError::NonRectangular {
+                row,
+                expected,
+                found,
+            } => write!(
+                f,
+                "row {}: expected {} cells like the first row, found {}",
+                row, expected, found
+            ),
+            ParseError::NoGuard => write!(
+                f,
+                "no guard found, expected one of '^', '>', 'v', '<' (or '1', '3', '7', '9' for diagonals)"
+            ),
+            ParseError::MultipleGuards { first, second } => {
+                write!(f, "multiple guards found at {:?} and {:?}", first, second)
             }
+            ParseError::UnpairedPortal { name, count } => write!(
+                f,
+                "portal '{}' must appear exactly twice, found {}",
+                name, count
+            ),
         }
     }
-    count
 }
 
-fn parse_file(file: &str) -> State {
-    let mut map = vec![];
-    let mut location: Option<_> = None;
+impl std::error::Error for ParseError {}
+
+fn parse_file(file: &str) -> Result<State, ParseError> {
+    let mut guards = parse_guards(file)?;
+    if guards.len() > 1 {
+        return Err(ParseError::MultipleGuards {
+            first: guards[0].guard_location,
+            second: guards[1].guard_location,
+        });
+    }
+    Ok(guards.remove(0))
+}
+
+/// Parse a map with one or more guards, returning one state per guard in reading order.
+/// Each state only has the starting cell of its own guard marked as walked.
+fn parse_guards(file: &str) -> Result<Vec<State>, ParseError> {
+    let mut map: Vec<Vec<Cell>> = vec![];
+    let mut guards: Vec<((i32, i32), Direction)> = vec![];
+    let mut portal_cells: BTreeMap<char, Vec<(i32, i32)>> = BTreeMap::new();
     for (row, line) in file.trim().lines().enumerate() {
-        map.push(
-            line.chars()
-                .enumerate()
-                .map(|(column, el)| match el {
-                    '#' => Cell::Block,
-                    '.' => Cell::Free,
-                    '^' => {
-                        location = Some((row as i32, column as i32));
-                        Cell::Walked(DirectionField::Up)
-                    }
-                    _ => panic!("Invalid cell"),
-                })
-                .collect(),
-        );
+        let mut cells = vec![];
+        for (column, el) in line.chars().enumerate() {
+            let location = (row as i32, column as i32);
+            cells.push(match el {
+                '#' => Cell::Block,
+                '.' => Cell::Free,
+                'A'..='Z' => {
+                    portal_cells.entry(el).or_default().push(location);
+                    Cell::Free
+                }
+                _ => {
+                    let direction = Direction::from_char(el).ok_or(ParseError::InvalidCell {
+                        row,
+                        column,
+                        found: el,
+                    })?;
+                    guards.push((location, direction));
+                    Cell::Free
+                }
+            });
+        }
+        if let Some(first) = map.first() {
+            if cells.len() != first.len() {
+                return Err(ParseError::NonRectangular {
+                    row,
+                    expected: first.len(),
+                    found: cells.len(),
+                });
+            }
+        }
+        map.push(cells);
     }
-    State {
-        map,
-        guard_location: location.unwrap(),
-        guard_direction: Direction::Up,
+    if map.is_empty() || map[0].is_empty() {
+        return Err(ParseError::EmptyMap);
     }
+    if guards.is_empty() {
+        return Err(ParseError::NoGuard);
+    }
+    let mut portals = HashMap::new();
+    for (name, cells) in portal_cells {
+        let &[first, second] = cells.as_slice() else {
+            return Err(ParseError::UnpairedPortal {
+                name,
+                count: cells.len(),
+            });
+        };
+        portals.insert(
+            first,
+            Portal {
+                name,
+                target: second,
+            },
+        );
+        portals.insert(
+            second,
+            Portal {
+                name,
+                target: first,
+            },
+        );
+    }
+    Ok(guards
+        .into_iter()
+        .map(|(guard_location, guard_direction)| {
+            let mut state = State {
+                map: map.clone(),
+                guard_location,
+                guard_direction,
+                turn_policy: TurnPolicy::Right,
+                boundary: Boundary::Exit,
+                portals: portals.clone(),
+                moves: 0,
+                turns: 0,
+                seen: HashSet::from([(guard_location, guard_direction, 0)]),
+            };
+            state.set_cell_at(guard_location, Cell::Walked(guard_direction.to_field()));
+            state
+        })
+        .collect())
 }
 
 #[cfg(test)]
@@ -246,7 +751,7 @@ mod tests {
 
     #[test]
     fn test_part1() {
-        let state = parse_file(get_test_map());
+        let state = parse_file(get_test_map()).unwrap();
         assert_eq!(state.map.len(), 10);
         for el in &state.map {
             assert_eq!(el.len(), 10);
@@ -275,8 +780,654 @@ mod tests {
 
     #[test]
     fn test_part2() {
-        let state = parse_file(get_test_map());
-        assert_eq!(count_obstacle_locations(state), 6);
+        let state = parse_file(get_test_map()).unwrap();
+        assert_eq!(count_obstacle_locations(&state), 6);
+        assert_eq!(count_obstacle_locations_parallel(&state, 4), 6);
+        assert_eq!(bench::count_obstacle_locations_naive(state), 6);
+    }
+
+    #[test]
+    fn test_parse_file() {
+        for (map, direction) in [("#.\n.>", Direction::Right), ("v.\n..", Direction::Down)] {
+            let state = parse_file(map).unwrap();
+            assert_eq!(state.guard_direction, direction);
+            assert_eq!(
+                state.cell_at(state.guard_location),
+                Cell::Walked(direction.to_field())
+            );
+        }
+        let mut state = parse_file("...\n<..\n...").unwrap();
+        assert_eq!(state.guard_location, (1, 0));
+        assert_eq!(count_walkable_cells(state.clone()), 1);
+        state.step();
+        assert_eq!(state.to_string(), "...\n<..\n...\n");
+
+        assert_eq!(parse_file("\n\n").err(), Some(ParseError::EmptyMap));
+        assert_eq!(parse_file("..\n..").err(), Some(ParseError::NoGuard));
+        assert_eq!(
+            parse_file("^.\n.x").err(),
+            Some(ParseError::InvalidCell {
+                row: 1,
+                column: 1,
+                found: 'x'
+            })
+        );
+        assert_eq!(
+            parse_file("^.\n.<").err(),
+            Some(ParseError::MultipleGuards {
+                first: (0, 0),
+                second: (1, 1)
+            })
+        );
+        let error = parse_file("^..\n..\n...").err().unwrap();
+        assert_eq!(
+            error,
+            ParseError::NonRectangular {
+                row: 1,
+                expected: 3,
+                found: 2
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "row 1: expected 3 cells like the first row, found 2"
+        );
+    }
+
+    #[test]
+    fn test_render() {
+        let mut state = parse_file(get_test_map()).unwrap();
+        assert_eq!(state.to_string(), get_test_map().to_string() + "\n");
+
+        state.run();
+        assert_eq!(
+            state.to_string(),
+            "\
+....#.....
+....+---+#
+....|...|.
+..#.|...|.
+..+-+-+#|.
+..|.|.|.|.
+.#+-+-+-+.
+.+----++#.
+#+----+|..
+......#v..
+"
+        );
+    }
+
+    #[test]
+    fn test_replay() {
+        let mut state = parse_file(".#.\n..#\n.^.").unwrap();
+        let mut output = vec![];
+        let result = render::replay(&mut state, &mut output, 2).unwrap();
+        assert!(matches!(result, StepResult::ReachedExit));
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "Step 0:\n.#.\n..#\n.^.\n\n\
+             Step 2:\n.#.\n.>#\n.|.\n\n\
+             Step 4:\n.#.\n.+#\n.v.\n\n\
+             Step 5:\n.#.\n.+#\n.v.\n\n"
+        );
+    }
+
+    #[test]
+    fn test_obstacle_locations() {
+        let state = parse_file(get_test_map()).unwrap();
+        let obstacles = find_obstacle_locations(&state, 2);
+        let locations: Vec<_> = obstacles.iter().map(|x| x.location).collect();
+        assert_eq!(
+            locations,
+            vec![(6, 3), (7, 6), (7, 7), (8, 1), (8, 3), (9, 7)]
+        );
+
+        // The first option from the puzzle: the guard loops right away around the starting area,
+        // 18 moves and 4 turns
+        assert_eq!(obstacles[0].entry, ((6, 4), Direction::Up));
+        assert_eq!(obstacles[0].period, 22);
+
+        assert_eq!(
+            render_obstacles(&state, &obstacles),
+            "\
+....#.....
+.........#
+..........
+..#.......
+.......#..
+..........
+.#.O^.....
+......OO#.
+#O.O......
+......#O..
+"
+        );
+    }
+
+    #[test]
+    fn test_obstacles_random_maps() {
+        // Compare against the brute force on random maps
+        for mut state in random_maps(50) {
+            for policy in [
+                TurnPolicy::Right,
+                TurnPolicy::Left,
+                TurnPolicy::Reverse,
+                TurnPolicy::Alternate,
+                TurnPolicy::Right45,
+                TurnPolicy::Left45,
+            ] {
+                state.turn_policy = policy;
+                let expected = bench::count_obstacle_locations_naive(state.clone());
+                assert_eq!(count_obstacle_locations(&state), expected);
+                assert_eq!(count_obstacle_locations_parallel(&state, 3), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_turn_policies() {
+        let state = parse_file("#...\n....\n^...").unwrap();
+        let walked = |policy| {
+            let mut state = state.clone();
+            state.turn_policy = policy;
+            count_walkable_cells(state)
+        };
+        assert_eq!(walked(TurnPolicy::Right), 5);
+        assert_eq!(walked(TurnPolicy::Left), 2);
+        assert_eq!(walked(TurnPolicy::Reverse), 2);
+        assert_eq!(walked(TurnPolicy::Alternate), 5);
+
+        // A guard that does not leave after visiting more states than possible is in a loop
+        for mut state in random_maps(100) {
+            for policy in [
+                TurnPolicy::Right,
+                TurnPolicy::Left,
+                TurnPolicy::Reverse,
+                TurnPolicy::Alternate,
+                TurnPolicy::Right45,
+                TurnPolicy::Left45,
+            ] {
+                state.turn_policy = policy;
+                let max_states = (state.num_rows() * state.num_columns()) as usize * 8 * 2;
+                let mut copy = state.clone();
+                let exits =
+                    (0..=max_states).any(|_| matches!(copy.step(), StepResult::ReachedExit));
+                assert_eq!(state.is_endless_loop(), !exits);
+            }
+        }
+    }
+
+    #[test]
+    fn test_diagonals() {
+        let mut state = parse_file("....\n.#..\n....\n...7").unwrap();
+        assert_eq!(state.guard_direction, Direction::UpLeft);
+        assert_eq!(state.advance((0, 0), Direction::UpLeft), None);
+        assert_eq!(state.advance((1, 1), Direction::DownRight), Some((2, 2)));
+        assert_eq!(Direction::Up.turn_clockwise(1), Direction::UpRight);
+        assert_eq!(Direction::UpLeft.turn_clockwise(1), Direction::Up);
+        assert_eq!(Direction::Left.rotate(), Direction::Up);
+
+        // The guard turns by 45 degrees in front of the block at (1, 1), then goes up
+        state.turn_policy = TurnPolicy::Right45;
+        state.run();
+        assert_eq!(state.to_string(), "..^.\n.#|.\n..*.\n...\\\n");
+        assert_eq!(state.count_walked(), 4);
+
+        // A diamond-shaped loop, walked diagonally
+        let mut state = parse_file("...#.\n#....\n.9...\n....#\n.#...").unwrap();
+        assert!(state.is_endless_loop());
+        state.turn_policy = TurnPolicy::Right45;
+        assert!(!state.is_endless_loop());
+    }
+
+    #[test]
+    fn test_run_result() {
+        let mut state = parse_file(get_test_map()).unwrap();
+        let mut copy = state.clone();
+        assert_eq!(
+            copy.run(),
+            RunResult::Exited {
+                moves: 44,
+                turns: 10,
+                exit: (9, 7)
+            }
+        );
+
+        // The first obstacle from the puzzle, as found by the obstacle search
+        state.set_cell_at((6, 3), Cell::Block);
+        let result = state.clone().run();
+        assert_eq!(
+            result,
+            RunResult::Cycle {
+                start: ((6, 4), Direction::Up),
+                period: 22
+            }
+        );
+        assert_eq!(
+            result.to_string(),
+            "the guard walks in a loop of 22 steps entered at (6, 4) going Up"
+        );
+        assert_eq!(count_walkable_cells(state), 18);
+
+        let mut state = parse_file("#.\n^.").unwrap();
+        state.set_cell_at((1, 0), Cell::Block);
+        assert_eq!(state.run(), RunResult::OnBlock { location: (1, 0) });
+
+        // Same loops as the obstacle search, for every turn policy
+        for mut state in random_maps(20) {
+            for policy in [TurnPolicy::Right, TurnPolicy::Alternate, TurnPolicy::Left45] {
+                state.turn_policy = policy;
+                if state.is_endless_loop() {
+                    continue;
+                }
+                for obstacle in find_obstacle_locations(&state, 1) {
+                    let mut copy = state.clone();
+                    copy.set_cell_at(obstacle.location, Cell::Block);
+                    assert_eq!(
+                        copy.run(),
+                        RunResult::Cycle {
+                            start: obstacle.entry,
+                            period: obstacle.period
+                        }
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_moves_and_turns() {
+        // Up into a dead-end pocket, two turns to face down, then out
+        let mut state = parse_file(".#.\n#.#\n.^.").unwrap();
+        assert_eq!(
+            state.run(),
+            RunResult::Exited {
+                moves: 2,
+                turns: 2,
+                exit: (2, 1)
+            }
+        );
+        assert_eq!((state.moves, state.turns), (2, 2));
+        assert_eq!(state.to_string(), ".#.\n#+#\n.v.\n");
+
+        // The same pocket, turning back at once
+        let mut state = parse_file(".#.\n#.#\n.^.").unwrap();
+        state.turn_policy = TurnPolicy::Reverse;
+        assert_eq!(
+            state.run(),
+            RunResult::Exited {
+                moves: 2,
+                turns: 1,
+                exit: (2, 1)
+            }
+        );
+
+        // Walls on three sides are never mistaken for a loop, whichever way the guard turns
+        for turn_policy in [TurnPolicy::Right, TurnPolicy::Left, TurnPolicy::Reverse] {
+            let mut state = parse_file(".#.\n#.#\n.^.").unwrap();
+            state.turn_policy = turn_policy;
+            assert!(!state.is_endless_loop(), "{:?}", turn_policy);
+            assert!(matches!(
+                state.run(),
+                RunResult::Exited { exit: (2, 1), .. }
+            ));
+        }
+        // Alternating turns only leave a pocket when the first turn faces the opening
+        let mut state = parse_file(".#.\n#^.\n.#.").unwrap();
+        state.turn_policy = TurnPolicy::Alternate;
+        assert!(!state.is_endless_loop());
+        assert_eq!(
+            state.run(),
+            RunResult::Exited {
+                moves: 1,
+                turns: 1,
+                exit: (1, 2)
+            }
+        );
+        // Otherwise the guard turns back and forth between two walls
+        let mut state = parse_file(".#.\n#.#\n.^.").unwrap();
+        state.turn_policy = TurnPolicy::Alternate;
+        assert!(state.is_endless_loop());
+
+        // A U-turn at the end of a corridor two cells wide
+        let mut state = parse_file("####\n#..#\n#..#\n#^.#\n#..#").unwrap();
+        assert_eq!(
+            state.run(),
+            RunResult::Exited {
+                moves: 6,
+                turns: 2,
+                exit: (4, 2)
+            }
+        );
+        assert_eq!(state.count_walked(), 7);
+
+        // Boxed in on all sides: turning on the spot is the only possible loop
+        let mut state = parse_file(".#.\n#^#\n.#.").unwrap();
+        assert_eq!(
+            state.run(),
+            RunResult::Cycle {
+                start: ((1, 1), Direction::Up),
+                period: 4
+            }
+        );
+        assert_eq!((state.moves, state.turns), (0, 4));
+    }
+
+    #[test]
+    fn test_heat_map() {
+        // Up, two turns in front of the blocks, then back down through the start
+        let state = parse_file("....\n.#..\n..#.\n.^..").unwrap();
+        let heat_map = heatmap::HeatMap::new(&state);
+        let mut csv = vec![];
+        heat_map.write_csv(&mut csv).unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "row,column,visits,directions\n2,1,1,^>v\n3,1,2,^v\n"
+        );
+
+        let mut ppm = vec![];
+        heat_map.write_ppm(&mut ppm, 2).unwrap();
+        let header = b"P6\n8 8\n255\n";
+        assert_eq!(&ppm[..header.len()], header);
+        let pixels = &ppm[header.len()..];
+        assert_eq!(pixels.len(), 8 * 8 * 3);
+        let pixel = |x: usize, y: usize| &pixels[(y * 8 + x) * 3..(y * 8 + x) * 3 + 3];
+        assert_eq!(pixel(0, 0), &[255, 255, 255]);
+        assert_eq!(pixel(3, 3), &[64, 64, 64]);
+        assert_eq!(pixel(2, 4), &[127, 0, 128]);
+        assert_eq!(pixel(3, 7), &[255, 0, 0]);
+    }
+
+    #[test]
+    fn test_placement() {
+        let state = parse_file(get_test_map()).unwrap();
+        assert_eq!(placement::minimum_trap(&state, 2), Some(vec![(6, 3)]));
+
+        // A single block only sends the guard out from another side
+        let state = parse_file(".#...\n....#\n.....\n.^...").unwrap();
+        assert_eq!(placement::minimum_trap(&state, 1), None);
+        let placed = placement::minimum_trap(&state, 2).unwrap();
+        assert_eq!(placed.len(), 2);
+        let mut trapped = state.clone();
+        for &x in &placed {
+            trapped.set_cell_at(x, Cell::Block);
+        }
+        assert!(trapped.is_endless_loop());
+
+        // A guard that already walks in circles needs no block
+        let state = parse_file(".A.\n...\n.^.\n.A.").unwrap();
+        assert_eq!(placement::minimum_trap(&state, 1), Some(vec![]));
+
+        // Turning the guard left in front of the last block leads it across the third row.
+        // Any second block traps it or makes the walk shorter.
+        let state = parse_file(".#...\n....#\n.....\n.^...").unwrap();
+        assert_eq!(count_walkable_cells(state.clone()), 7);
+        assert_eq!(placement::maximize_walked(&state, 2), (vec![(3, 3)], 8));
+        let mut longer = state.clone();
+        longer.set_cell_at((3, 3), Cell::Block);
+        assert_eq!(count_walkable_cells(longer), 8);
+
+        // No single block makes the walk longer, so placing them one at a time would stop at
+        // none; two blocks together turn the guard back down
+        let state = parse_file("...\n.^.\n...\n...").unwrap();
+        assert_eq!(placement::maximize_walked(&state, 1), (vec![], 2));
+        assert_eq!(
+            placement::maximize_walked(&state, 2),
+            (vec![(0, 1), (1, 2)], 3)
+        );
+    }
+
+    #[test]
+    fn test_wrap() {
+        let mut state = parse_file("#...\n....\n^...").unwrap();
+        assert!(!state.is_endless_loop());
+        state.boundary = Boundary::Wrap;
+        assert_eq!(state.advance((0, 1), Direction::Up), Some((2, 1)));
+        assert_eq!(state.advance((2, 3), Direction::DownRight), Some((0, 0)));
+
+        // Up, turn in front of the block, then around the second row forever
+        for _ in 0..5 {
+            assert!(matches!(state.step(), StepResult::Ok));
+        }
+        assert!(matches!(state.step(), StepResult::Cycle));
+        assert_eq!(state.guard_location, (1, 0));
+        assert_eq!(state.guard_direction, Direction::Right);
+        assert_eq!(state.to_string(), "#...\n>---\n|...\n");
+        assert_eq!(count_walkable_cells(state), 5);
+    }
+
+    #[test]
+    fn test_portals() {
+        // Into the portal at the top, out of the one at the bottom left and up to the exit
+        let state = parse_file(".A.\n...\n.^.\nA..").unwrap();
+        assert_eq!(state.teleport((0, 1)), (3, 0));
+        assert_eq!(state.teleport((3, 0)), (0, 1));
+        assert_eq!(state.teleport((1, 1)), (1, 1));
+        let mut copy = state.clone();
+        copy.run();
+        assert_eq!(copy.to_string(), "^A.\n||.\n||.\nA..\n");
+        assert_eq!(count_walkable_cells(state), 7);
+
+        // Walking out of a portal straight into its pair: a block on the way up ends the loop,
+        // a block anywhere else keeps it
+        let state = parse_file(".A.\n...\n.^.\n.A.").unwrap();
+        assert!(state.is_endless_loop());
+        assert_eq!(bench::count_obstacle_locations_naive(state.clone()), 8);
+        assert_eq!(count_obstacle_locations(&state), 8);
+        assert_eq!(count_obstacle_locations_parallel(&state, 3), 8);
+        assert_eq!(find_obstacle_locations(&state, 1).len(), 8);
+
+        // Blocking the way up sends the guard through the portal on its right, back to its left
+        let state = parse_file(".....\nA^.A.\n.....").unwrap();
+        assert!(!state.is_endless_loop());
+        let obstacles = find_obstacle_locations(&state, 1);
+        assert_eq!(obstacles.len(), 1);
+        assert_eq!(obstacles[0].location, (0, 1));
+        assert_eq!(obstacles[0].entry, ((1, 1), Direction::Right));
+        assert_eq!(bench::count_obstacle_locations_naive(state), 1);
+
+        assert_eq!(
+            parse_file("^A.").err(),
+            Some(ParseError::UnpairedPortal {
+                name: 'A',
+                count: 1
+            })
+        );
+        assert_eq!(
+            parse_file("^BB\nB..").err().unwrap().to_string(),
+            "portal 'B' must appear exactly twice, found 3"
+        );
+    }
+
+    #[test]
+    fn test_portals_random_maps() {
+        for mut state in random_maps_with_portals(50, "AB") {
+            for policy in [
+                TurnPolicy::Right,
+                TurnPolicy::Alternate,
+                TurnPolicy::Right45,
+            ] {
+                state.turn_policy = policy;
+                let expected = bench::count_obstacle_locations_naive(state.clone());
+                assert_eq!(count_obstacle_locations(&state), expected);
+                assert_eq!(count_obstacle_locations_parallel(&state, 3), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_cycle_detection() {
+        // A cycle is reported exactly when the guard is back in a previous place, direction and
+        // phase, across wraps and teleports
+        for mut state in random_maps_with_portals(50, "AB") {
+            for boundary in [Boundary::Exit, Boundary::Wrap] {
+                for policy in [TurnPolicy::Right, TurnPolicy::Alternate, TurnPolicy::Left45] {
+                    state.boundary = boundary;
+                    state.turn_policy = policy;
+                    let phases = policy.phases();
+                    let mut copy = state.clone();
+                    let mut seen = HashSet::from([(copy.guard_location, copy.guard_direction, 0)]);
+                    loop {
+                        let result = copy.step();
+                        let new = seen.insert((
+                            copy.guard_location,
+                            copy.guard_direction,
+                            copy.turns % phases,
+                        ));
+                        match result {
+                            StepResult::Ok => assert!(new),
+                            StepResult::Cycle => {
+                                assert!(!new);
+                                break;
+                            }
+                            StepResult::ReachedExit => {
+                                assert_eq!(boundary, Boundary::Exit);
+                                break;
+                            }
+                            StepResult::OnBlock => panic!("The guard cannot be on a block"),
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_guards() {
+        use guards::GuardOutcome;
+
+        let map = "......\n.>..<.\n......";
+        let run = |meeting| {
+            let mut guards = parse_guards(map).unwrap();
+            let outcomes = guards::run_guards(&mut guards, meeting);
+            (outcomes, guards::combined_coverage(&guards))
+        };
+        // Through each other and out of the opposite sides
+        assert_eq!(
+            run(Meeting::Pass),
+            (
+                vec![
+                    GuardOutcome::Exited { steps: 5 },
+                    GuardOutcome::Exited { steps: 5 }
+                ],
+                6
+            )
+        );
+        // Face to face after the first step, so both turn right and leave
+        assert_eq!(
+            run(Meeting::Block),
+            (
+                vec![
+                    GuardOutcome::Exited { steps: 4 },
+                    GuardOutcome::Exited { steps: 4 }
+                ],
+                6
+            )
+        );
+        // Walking past each other in the second step
+        assert_eq!(
+            run(Meeting::Stop),
+            (
+                vec![
+                    GuardOutcome::Stopped {
+                        steps: 2,
+                        location: (1, 3)
+                    },
+                    GuardOutcome::Stopped {
+                        steps: 2,
+                        location: (1, 2)
+                    }
+                ],
+                4
+            )
+        );
+
+        // Each guard only has its own starting cell walked
+        let guards = parse_guards(map).unwrap();
+        assert_eq!(guards.len(), 2);
+        assert_eq!(guards[1].to_string(), "......\n....<.\n......\n");
+        assert_eq!(guards[1].count_walked(), 1);
+
+        // The first guard loops in a box, the second one leaves right away
+        let map = ".#...\n....#\n#^...\n<..#.";
+        for meeting in [Meeting::Pass, Meeting::Block, Meeting::Stop] {
+            let mut guards = parse_guards(map).unwrap();
+            let outcomes = guards::run_guards(&mut guards, meeting);
+            assert!(matches!(outcomes[0], GuardOutcome::Cycle { .. }));
+            assert_eq!(outcomes[1], GuardOutcome::Exited { steps: 1 });
+        }
+    }
+
+    #[test]
+    fn test_guards_random_maps() {
+        use guards::GuardOutcome;
+
+        // Guards that pass through each other behave as if they were alone
+        for state in random_maps(50) {
+            let mut map = state.to_string();
+            let Some(free) = map.rfind('.') else {
+                continue;
+            };
+            map.replace_range(free..free + 1, ">");
+            let mut guards = parse_guards(&map).unwrap();
+            let alone: Vec<State> = guards.clone();
+            let outcomes = guards::run_guards(&mut guards, Meeting::Pass);
+            let mut covered = HashSet::new();
+            for ((guard, mut alone), outcome) in guards.iter().zip(alone).zip(outcomes) {
+                assert_eq!(
+                    alone.is_endless_loop(),
+                    matches!(outcome, GuardOutcome::Cycle { .. })
+                );
+                while let StepResult::Ok = alone.step() {}
+                assert_eq!(guard.count_walked(), alone.count_walked());
+                for row in 0..guard.num_rows() {
+                    for column in 0..guard.num_columns() {
+                        if matches!(guard.cell_at((row, column)), Cell::Walked(_)) {
+                            covered.insert((row, column));
+                        }
+                    }
+                }
+            }
+            assert_eq!(guards::combined_coverage(&guards), covered.len() as i32);
+        }
+    }
+
+    /// Random maps with a guard pointing up
+    fn random_maps(count: usize) -> Vec<State> {
+        random_maps_with_portals(count, "")
+    }
+
+    /// Random maps with a guard pointing up and a pair of cells for each portal name
+    fn random_maps_with_portals(count: usize, names: &str) -> Vec<State> {
+        let mut seed: u64 = 7;
+        let mut random = |n: u64| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+            (seed >> 33) % n
+        };
+        let mut maps = vec![];
+        while maps.len() < count {
+            let mut map = String::new();
+            for _ in 0..12 {
+                for _ in 0..15 {
+                    map.push(if random(6) == 0 { '#' } else { '.' });
+                }
+                map.push('\n');
+            }
+            let guard = random(12 * 16) as usize;
+            if map.as_bytes()[guard] == b'.' {
+                map.replace_range(guard..guard + 1, "^");
+                for name in names.chars().flat_map(|x| [x, x]) {
+                    let cell = random(12 * 16) as usize;
+                    if map.as_bytes()[cell] == b'.' {
+                        map.replace_range(cell..cell + 1, &name.to_string());
+                    }
+                }
+                if let Ok(state) = parse_file(&map) {
+                    maps.push(state);
+                }
+            }
+        }
+        maps
     }
 
     fn get_test_map() -> &'static str {
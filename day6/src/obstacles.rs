@@ -0,0 +1,305 @@
+use crate::{Boundary, Cell, Direction, State, StepResult, TurnPolicy};
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+/// For every cell and direction, where the guard stops walking straight: the last cell before a
+/// block, or `None` if it leaves the map
+struct JumpTable {
+    num_columns: i32,
+    stops: Vec<[Option<(i32, i32)>; Direction::ALL.len()]>,
+}
+
+impl JumpTable {
+    fn new(state: &State) -> JumpTable {
+        let num_rows = state.num_rows();
+        let num_columns = state.num_columns();
+        let mut table = JumpTable {
+            num_columns,
+            stops: vec![[None; Direction::ALL.len()]; (num_rows * num_columns) as usize],
+        };
+
+        // Sweep each line starting from the side the guard is walking towards
+        for direction in Direction::ALL {
+            let (row_delta, column_delta) = direction.delta();
+            let rows: Vec<i32> = if row_delta > 0 {
+                (0..num_rows).rev().collect()
+            } else {
+                (0..num_rows).collect()
+            };
+            let columns: Vec<i32> = if column_delta > 0 {
+                (0..num_columns).rev().collect()
+            } else {
+                (0..num_columns).collect()
+            };
+            for &row in &rows {
+                for &column in &columns {
+                    let location = (row, column);
+                    let stop = match state.advance(location, direction) {
+                        None => None,
+                        Some(next) if state.cell_at(next) == Cell::Block => Some(location),
+                        Some(next) => table.stop(next, direction),
+                    };
+                    let index = table.index(location);
+                    table.stops[index][direction.index()] = stop;
+                }
+            }
+        }
+        table
+    }
+
+    fn index(&self, location: (i32, i32)) -> usize {
+        (location.0 * self.num_columns + location.1) as usize
+    }
+
+    fn stop(&self, location: (i32, i32), direction: Direction) -> Option<(i32, i32)> {
+        self.stops[self.index(location)][direction.index()]
+    }
+}
+
+/// A possible obstacle location, with the guard state right before walking into it
+struct Candidate {
+    obstacle: (i32, i32),
+    guard_location: (i32, i32),
+    guard_direction: Direction,
+    turns: usize,
+}
+
+/// Walk the original path and collect the cells the guard enters, in order.
+/// An obstacle anywhere else would never be touched, so it cannot change the route.
+/// If the original path is already a loop, it stops after going around once: the guard then stays
+/// trapped with an obstacle anywhere else, see `find_loops`.
+fn find_candidates(state: &State) -> Vec<Candidate> {
+    let mut state = state.clone();
+    let mut candidates = vec![];
+    loop {
+        let guard_location = state.guard_location;
+        let guard_direction = state.guard_direction;
+        let Some(next) = state.advance(guard_location, guard_direction) else {
+            return candidates;
+        };
+        if state.cell_at(next) == Cell::Free && !state.portals.contains_key(&next) {
+            // First visit: the route up to here is not affected by an obstacle in `next`
+            candidates.push(Candidate {
+                obstacle: next,
+                guard_location,
+                guard_direction,
+                turns: state.turns,
+            });
+        }
+        match state.step() {
+            StepResult::Ok => {}
+            StepResult::ReachedExit | StepResult::Cycle | StepResult::OnBlock => return candidates,
+        }
+    }
+}
+
+/// Whether the guard would walk in circles with an extra block in `candidate.obstacle`.
+/// Only the turning points are tracked: `visited` holds, for every cell, direction and phase of
+/// the turn policy, the id of the last trial that turned there, so it never needs to be cleared.
+fn is_endless_loop(
+    table: &JumpTable,
+    policy: TurnPolicy,
+    candidate: &Candidate,
+    trial: u32,
+    visited: &mut [u32],
+) -> bool {
+    let obstacle = candidate.obstacle;
+    let phases = policy.phases();
+    let mut location = candidate.guard_location;
+    let mut direction = candidate.guard_direction;
+    let mut turns = candidate.turns;
+    loop {
+        let stop = table.stop(location, direction);
+
+        // Stop earlier if the new obstacle is on the way
+        let obstacle_distance = distance_along(location, obstacle, direction);
+        let blocked_by_obstacle = obstacle_distance.is_some_and(|x| {
+            stop.is_none_or(|stop| distance_along(location, stop, direction).unwrap_or(0) >= x)
+        });
+        location = if blocked_by_obstacle {
+            let (row_delta, column_delta) = direction.delta();
+            (obstacle.0 - row_delta, obstacle.1 - column_delta)
+        } else {
+            match stop {
+                Some(x) => x,
+                None => return false,
+            }
+        };
+        direction = policy.turn(direction, turns);
+        turns += 1;
+
+        let index = (table.index(location) * Direction::ALL.len() + direction.index()) * phases
+            + turns % phases;
+        if visited[index] == trial {
+            return true;
+        }
+        visited[index] = trial;
+    }
+}
+
+/// Number of steps to go from `from` to `to` walking in a straight line, if `to` is ahead
+fn distance_along(from: (i32, i32), to: (i32, i32), direction: Direction) -> Option<i32> {
+    let (row_delta, column_delta) = direction.delta();
+    let distance = if row_delta != 0 {
+        (to.0 - from.0) * row_delta
+    } else {
+        (to.1 - from.1) * column_delta
+    };
+    let reached = (
+        from.0 + distance * row_delta,
+        from.1 + distance * column_delta,
+    ) == to;
+    (reached && distance > 0).then_some(distance)
+}
+
+/// Count the locations where a new block would trap the guard in a loop
+pub fn count_obstacle_locations(state: &State) -> i32 {
+    count_obstacle_locations_parallel(state, 1)
+}
+
+/// Same as `count_obstacle_locations`, evaluating the candidates on `threads` threads
+pub fn count_obstacle_locations_parallel(state: &State, threads: usize) -> i32 {
+    find_loops(state, threads).len() as i32
+}
+
+/// An obstacle location that traps the guard, with the loop it creates
+#[derive(PartialEq, Debug)]
+pub struct LoopObstacle {
+    pub location: (i32, i32),
+    // First guard location and direction that is part of the loop
+    pub entry: ((i32, i32), Direction),
+    // Number of steps (moves and turns) to go around the loop once
+    pub period: usize,
+}
+
+/// All the locations where a new block would trap the guard, sorted by row and column
+pub fn find_obstacle_locations(state: &State, threads: usize) -> Vec<LoopObstacle> {
+    let mut obstacles: Vec<_> = find_loops(state, threads)
+        .into_iter()
+        .map(|x| trace_loop(state, x).expect("The obstacle must cause a loop"))
+        .collect();
+    obstacles.sort_by_key(|x| x.location);
+    obstacles
+}
+
+/// Simulate the guard from the start with an extra block, step by step.
+/// The first repeated guard state is where the loop starts.
+fn trace_loop(state: &State, obstacle: (i32, i32)) -> Option<LoopObstacle> {
+    let mut location = state.guard_location;
+    let mut direction = state.guard_direction;
+    let mut turns = state.turns;
+    let phases = state.turn_policy.phases();
+    let mut seen = HashMap::new();
+    for step in 0.. {
+        if let Some(previous) = seen.insert((location, direction, turns % phases), step) {
+            return Some(LoopObstacle {
+                location: obstacle,
+                entry: (location, direction),
+                period: step - previous,
+            });
+        }
+        let next = state.advance(location, direction)?;
+        if next == obstacle || state.cell_at(next) == Cell::Block {
+            direction = state.turn_policy.turn(direction, turns);
+            turns += 1;
+        } else {
+            location = state.teleport(next);
+        }
+    }
+    unreachable!()
+}
+
+/// Draw the map with the new obstacles marked as `O`
+pub fn render_obstacles(state: &State, obstacles: &[LoopObstacle]) -> String {
+    let mut lines: Vec<Vec<char>> = state
+        .to_string()
+        .lines()
+        .map(|x| x.chars().collect())
+        .collect();
+    for obstacle in obstacles {
+        let (row, column) = obstacle.location;
+        lines[row as usize][column as usize] = 'O';
+    }
+    lines
+        .into_iter()
+        .map(|x| x.into_iter().collect::<String>() + "\n")
+        .collect()
+}
+
+/// Locations of the candidates that cause a loop, in the order the guard reaches them, followed by
+/// the cells off the route when the guard already walks in circles without a new block.
+/// Candidates are handed out in small batches to a pool of scoped threads; each result is stored
+/// at the candidate index, so the output does not depend on the scheduling.
+/// The jump tables only describe straight walks to the edge, so maps that wrap around or have
+/// portals are simulated step by step.
+fn find_loops(state: &State, threads: usize) -> Vec<(i32, i32)> {
+    const BATCH_SIZE: usize = 64;
+
+    let table = (state.boundary == Boundary::Exit && state.portals.is_empty())
+        .then(|| JumpTable::new(state));
+    let candidates = find_candidates(state);
+    let next_batch = AtomicUsize::new(0);
+
+    let evaluate = || {
+        let mut visited = table.as_ref().map_or(vec![], |x| {
+            vec![0; x.stops.len() * Direction::ALL.len() * state.turn_policy.phases()]
+        });
+        let mut results = vec![];
+        loop {
+            let start = next_batch.fetch_add(BATCH_SIZE, Ordering::Relaxed);
+            if start >= candidates.len() {
+                return results;
+            }
+            let batch = &candidates[start..(start + BATCH_SIZE).min(candidates.len())];
+            for (i, candidate) in (start..).zip(batch) {
+                let trial = i as u32 + 1;
+                let is_loop = match &table {
+                    Some(table) => {
+                        is_endless_loop(table, state.turn_policy, candidate, trial, &mut visited)
+                    }
+                    None => trace_loop(state, candidate.obstacle).is_some(),
+                };
+                results.push((i, is_loop));
+            }
+        }
+    };
+
+    let mut loops = vec![false; candidates.len()];
+    if threads <= 1 {
+        for (i, result) in evaluate() {
+            loops[i] = result;
+        }
+    } else {
+        thread::scope(|scope| {
+            let workers: Vec<_> = (0..threads).map(|_| scope.spawn(evaluate)).collect();
+            for worker in workers {
+                for (i, result) in worker.join().unwrap() {
+                    loops[i] = result;
+                }
+            }
+        });
+    }
+    let mut obstacles: Vec<(i32, i32)> = candidates
+        .iter()
+        .zip(loops)
+        .filter(|(_, is_loop)| *is_loop)
+        .map(|(x, _)| x.obstacle)
+        .collect();
+    if state.is_endless_loop() {
+        // An obstacle the guard never reaches keeps it on the same loop
+        let reached: HashSet<(i32, i32)> = candidates.iter().map(|x| x.obstacle).collect();
+        for row in 0..state.num_rows() {
+            for column in 0..state.num_columns() {
+                let location = (row, column);
+                if state.cell_at(location) == Cell::Free
+                    && !state.portals.contains_key(&location)
+                    && !reached.contains(&location)
+                {
+                    obstacles.push(location);
+                }
+            }
+        }
+    }
+    obstacles
+}
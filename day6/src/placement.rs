@@ -0,0 +1,101 @@
+use crate::{Cell, RunResult, State};
+use std::collections::HashSet;
+
+/// Cells where a new block could change the route: the ones the guard enters, apart from portals.
+/// Blocks anywhere else are never touched.
+fn path_cells(state: &State) -> Vec<(i32, i32)> {
+    let mut walked = state.clone();
+    walked.run();
+    let mut cells = vec![];
+    for row in 0..state.num_rows() {
+        for column in 0..state.num_columns() {
+            let location = (row, column);
+            if state.cell_at(location) == Cell::Free
+                && matches!(walked.cell_at(location), Cell::Walked(_))
+                && !state.portals.contains_key(&location)
+            {
+                cells.push(location);
+            }
+        }
+    }
+    cells
+}
+
+fn with_blocks(state: &State, blocks: &[(i32, i32)]) -> State {
+    let mut state = state.clone();
+    for &location in blocks {
+        state.set_cell_at(location, Cell::Block);
+    }
+    state
+}
+
+/// The smallest set of new blocks that keeps the guard from ever leaving, trying up to
+/// `max_blocks` blocks. Sets of the same size are tried breadth first, each new block being on
+/// the route left by the previous ones, so the first one found is as small as possible.
+pub fn minimum_trap(state: &State, max_blocks: usize) -> Option<Vec<(i32, i32)>> {
+    let mut level: Vec<Vec<(i32, i32)>> = vec![vec![]];
+    for blocks in 0..=max_blocks {
+        let mut next = vec![];
+        let mut seen = HashSet::new();
+        for placed in &level {
+            let trial = with_blocks(state, placed);
+            if trial.is_endless_loop() {
+                return Some(placed.clone());
+            }
+            if blocks == max_blocks {
+                continue;
+            }
+            for cell in path_cells(&trial) {
+                let mut extended = placed.clone();
+                extended.push(cell);
+                extended.sort();
+                if seen.insert(extended.clone()) {
+                    next.push(extended);
+                }
+            }
+        }
+        level = next;
+    }
+    None
+}
+
+/// The set of up to `max_blocks` new blocks that makes the guard walk over the most cells while
+/// still leaving the map. Every set is tried breadth first, each new block being on the route left
+/// by the previous ones: a block the guard never reaches changes nothing, and the last block it
+/// reaches is on the route of the others. Ties keep the smallest set found first.
+/// Returns the blocks and the number of walked cells.
+pub fn maximize_walked(state: &State, max_blocks: usize) -> (Vec<(i32, i32)>, i32) {
+    let mut best = (vec![], count_walked(state).unwrap_or(0));
+    let mut level: Vec<Vec<(i32, i32)>> = vec![vec![]];
+    for _ in 0..max_blocks {
+        let mut next = vec![];
+        let mut seen = HashSet::new();
+        for placed in &level {
+            for cell in path_cells(&with_blocks(state, placed)) {
+                let mut extended = placed.clone();
+                extended.push(cell);
+                extended.sort();
+                if !seen.insert(extended.clone()) {
+                    continue;
+                }
+                if let Some(walked) = count_walked(&with_blocks(state, &extended)) {
+                    if walked > best.1 {
+                        best = (extended.clone(), walked);
+                    }
+                }
+                next.push(extended);
+            }
+        }
+        level = next;
+    }
+    best
+}
+
+/// Number of cells walked by the guard, if it leaves the map
+fn count_walked(state: &State) -> Option<i32> {
+    let mut state = state.clone();
+    match state.run() {
+        RunResult::Exited { .. } => Some(state.count_walked()),
+        _ => None,
+    }
+}
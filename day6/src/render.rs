@@ -0,0 +1,72 @@
+use crate::{Cell, DirectionField, State, StepResult};
+use std::fmt::{Display, Formatter};
+use std::io::Write;
+
+impl Cell {
+    /// Same symbols as the puzzle: `|` and `-` for walked cells, `+` where the path crosses.
+    /// Diagonal paths use `/` and `\`, crossing as `X`, and `*` for any other mix.
+    fn to_char(self) -> char {
+        match self {
+            Cell::Free => '.',
+            Cell::Block => '#',
+            Cell::Walked(directions) => {
+                let lines = [
+                    ('|', DirectionField::Up | DirectionField::Down),
+                    ('-', DirectionField::Left | DirectionField::Right),
+                    ('/', DirectionField::UpRight | DirectionField::DownLeft),
+                    ('\\', DirectionField::UpLeft | DirectionField::DownRight),
+                ];
+                let mut walked = lines.iter().filter(|(_, x)| directions.intersects(*x));
+                match (walked.next(), walked.next(), walked.next()) {
+                    (None, _, _) => '.',
+                    (Some((c, _)), None, _) => *c,
+                    // Crossing orthogonal paths only, as in the puzzle
+                    (Some(('|', _)), Some(('-', _)), None) => '+',
+                    // Crossing diagonal paths only
+                    (Some(('/', _)), Some(('\\', _)), None) => 'X',
+                    _ => '*',
+                }
+            }
+        }
+    }
+}
+
+impl Display for State {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (row, cells) in self.map.iter().enumerate() {
+            for (column, cell) in cells.iter().enumerate() {
+                let location = (row as i32, column as i32);
+                if location == self.guard_location {
+                    write!(f, "{}", self.guard_direction.to_char())?;
+                } else if let Some(portal) = self.portals.get(&location) {
+                    write!(f, "{}", portal.name)?;
+                } else {
+                    write!(f, "{}", cell.to_char())?;
+                }
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Run the simulation writing a frame every `every` steps, plus the first and the last one
+pub fn replay(
+    state: &mut State,
+    out: &mut impl Write,
+    every: usize,
+) -> std::io::Result<StepResult> {
+    assert!(every > 0);
+    writeln!(out, "Step 0:\n{}", state)?;
+    for step in 1.. {
+        let result = state.step();
+        let finished = !matches!(result, StepResult::Ok);
+        if finished || step % every == 0 {
+            writeln!(out, "Step {}:\n{}", step, state)?;
+        }
+        if finished {
+            return Ok(result);
+        }
+    }
+    unreachable!()
+}
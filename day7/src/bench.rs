@@ -0,0 +1,28 @@
+use crate::operators::{PART1_OPERATORS, PART2_OPERATORS};
+use crate::{count_valid, count_valid_backward, Equation};
+use std::time::Instant;
+
+/// Compare the backward solver against the original forward search on the given equations
+pub fn run(equations: &[Equation]) {
+    for (name, operators) in [
+        ("Part 1", &PART1_OPERATORS[..]),
+        ("Part 2", &PART2_OPERATORS[..]),
+    ] {
+        let start = Instant::now();
+        let forward = count_valid(equations, operators);
+        let forward_time = start.elapsed();
+
+        let start = Instant::now();
+        let backward = count_valid_backward(equations, operators);
+        let backward_time = start.elapsed();
+
+        assert_eq!(forward, backward, "The implementations disagree");
+        println!("{}: {}", name, backward);
+        println!("Forward search: {:?}", forward_time);
+        println!(
+            "Backward search: {:?} ({:.1}x)",
+            backward_time,
+            forward_time.as_secs_f64() / backward_time.as_secs_f64()
+        );
+    }
+}
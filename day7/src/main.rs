@@ -1,15 +1,127 @@
+mod bench;
+mod operators;
+
+use operators::{Operator, PART1_OPERATORS, PART2_OPERATORS};
+use std::fmt::{Display, Formatter};
+
 struct Equation {
     result: u64,
     values: Vec<u64>,
 }
 
+/// The values of an equation with the operators between them, like `81 + 40 * 27`
+struct Expression<'a> {
+    values: &'a [u64],
+    operators: &'a [&'a dyn Operator],
+}
+
+impl Expression<'_> {
+    /// The value from left to right, `None` if an operator cannot be applied
+    fn evaluate(&self) -> Option<u64> {
+        self.operators
+            .iter()
+            .zip(&self.values[1..])
+            .try_fold(self.values[0], |accumulator, (operator, value)| {
+                operator.apply(accumulator, *value)
+            })
+    }
+}
+
+impl Display for Expression<'_> {
+    /// With `{:#}`, parentheses are added wherever the usual precedence of the operators would
+    /// give a different order than left to right
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut text = self.values[0].to_string();
+        // Precedence of the operator applied last, none for a single value
+        let mut left = None;
+        for (operator, value) in self.operators.iter().zip(&self.values[1..]) {
+            let precedence = operator.precedence().unwrap_or(u8::MAX);
+            if f.alternate() && left.is_some_and(|x| x < precedence) {
+                text = format!("({})", text);
+            }
+            text = format!("{} {} {}", text, operator.symbol(), value);
+            left = Some(precedence);
+        }
+        write!(f, "{}", text)
+    }
+}
+
 fn main() {
     let state = parse_file(include_str!("input.txt"));
-    println!("Part 1: {}", count_valid(&state, part1_operators));
-    println!("Part 2: {}", count_valid(&state, part2_operators));
+    let mut command = None;
+    let mut operators: Vec<&dyn Operator> = PART2_OPERATORS.to_vec();
+    let mut all = false;
+    let mut parentheses = false;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--part1" => operators = PART1_OPERATORS.to_vec(),
+            "--operators" => {
+                operators = args
+                    .next()
+                    .expect("Expected a list of operators")
+                    .split(',')
+                    .map(|x| {
+                        operators::from_symbol(x)
+                            .unwrap_or_else(|| panic!("Unknown operator '{}'", x))
+                    })
+                    .collect()
+            }
+            "--all" => all = true,
+            "--parentheses" => parentheses = true,
+            _ if command.is_none() => command = Some(arg),
+            _ => panic!("Unexpected argument '{}'", arg),
+        }
+    }
+
+    match command.as_deref() {
+        None => {
+            println!("Part 1: {}", count_valid_backward(&state, &PART1_OPERATORS));
+            println!("Part 2: {}", count_valid_backward(&state, &PART2_OPERATORS));
+        }
+        Some("count") => {
+            println!("Total: {}", count_valid_backward(&state, &operators));
+        }
+        Some("bench") => bench::run(&state),
+        Some("solutions") => {
+            // Show how each equation can be solved, checking the solutions again
+            let mut sum = 0;
+            for equation in &state {
+                let solutions = if all {
+                    find_all_solutions(equation, &operators)
+                } else {
+                    find_solution(equation, &operators).into_iter().collect()
+                };
+                if solutions.is_empty() {
+                    continue;
+                }
+                for x in &solutions {
+                    let expression = Expression {
+                        values: &equation.values,
+                        operators: x,
+                    };
+                    assert_eq!(expression.evaluate(), Some(equation.result));
+                    if parentheses {
+                        println!("{} = {:#}", equation.result, expression);
+                    } else {
+                        println!("{} = {}", equation.result, expression);
+                    }
+                }
+                if all {
+                    println!("{} solutions for {}", solutions.len(), equation.result);
+                }
+                sum += equation.result;
+            }
+            println!("Total: {}", sum);
+        }
+        Some(arg) => panic!(
+            "Invalid argument '{}', expected: [count|solutions|bench] [--part1] [--operators LIST] [--all] [--parentheses]",
+            arg
+        ),
+    }
 }
 
-fn count_valid(equations: &Vec<Equation>, operators: fn(u64, u64) -> Vec<u64>) -> u64 {
+fn count_valid(equations: &[Equation], operators: &[&dyn Operator]) -> u64 {
     let mut sum = 0;
     for equation in equations {
         if may_be_valid(equation, operators) {
@@ -19,48 +131,155 @@ fn count_valid(equations: &Vec<Equation>, operators: fn(u64, u64) -> Vec<u64>) -
     sum
 }
 
-fn part1_operators(a: u64, b: u64) -> Vec<u64> {
-    vec![a + b, a * b]
-}
-
-fn part2_operators(a: u64, b: u64) -> Vec<u64> {
-    vec![a + b, a * b, int_concat(a, b)]
-}
-
-fn int_concat(a: u64, b: u64) -> u64 {
-    let exponent = b.ilog(10);
-    let multiplier = 10_u64.pow(exponent + 1);
-    a * multiplier + b
+/// `a || b`: the digits of `a` followed by the digits of `b`
+fn int_concat(a: u64, b: u64) -> Option<u64> {
+    let exponent = b.checked_ilog10().unwrap_or(0);
+    let multiplier = 10_u64.checked_pow(exponent + 1)?;
+    a.checked_mul(multiplier)?.checked_add(b)
 }
 
-fn may_be_valid(equation: &Equation, operators: fn(u64, u64) -> Vec<u64>) -> bool {
+fn may_be_valid(equation: &Equation, operators: &[&dyn Operator]) -> bool {
     // This method is only valid if all values are positive
     assert!(!equation.values.contains(&0));
     assert!(!equation.values.is_empty());
 
     fn validate(
         equation: &Equation,
-        operators: fn(u64, u64) -> Vec<u64>,
+        operators: &[&dyn Operator],
+        increasing: bool,
         index: usize,
         accumulator: u64,
     ) -> bool {
-        if accumulator > equation.result {
+        if index == equation.values.len() {
+            return accumulator == equation.result;
+        }
+        if increasing && accumulator > equation.result {
             // Optimization: this path cannot be the result
             return false;
         }
         let el = equation.values[index];
-        let values = operators(accumulator, el);
-        if index + 1 == equation.values.len() {
-            // This is the last element
-            values.contains(&equation.result)
-        } else {
-            values
-                .iter()
-                .any(|x| validate(equation, operators, index + 1, *x))
+        operators.iter().any(|operator| {
+            operator
+                .apply(accumulator, el)
+                .is_some_and(|x| validate(equation, operators, increasing, index + 1, x))
+        })
+    }
+
+    let increasing = operators.iter().all(|x| x.is_increasing());
+    validate(equation, operators, increasing, 1, equation.values[0])
+}
+
+fn count_valid_backward(equations: &[Equation], operators: &[&dyn Operator]) -> u64 {
+    equations
+        .iter()
+        .filter(|x| may_be_valid_backward(x, operators))
+        .map(|x| x.result)
+        .sum()
+}
+
+/// Same as `may_be_valid`, starting from the result and undoing the operators from the last value.
+/// Most operators cannot be undone (the value is not a divisor, or not a suffix), so far fewer
+/// branches are explored than going forward. Falls back to `may_be_valid` unless every operator
+/// has an inverse.
+fn may_be_valid_backward(equation: &Equation, operators: &[&dyn Operator]) -> bool {
+    // This method is only valid if all values are positive
+    assert!(!equation.values.contains(&0));
+    assert!(!equation.values.is_empty());
+    if !operators.iter().all(|x| x.has_inverse()) {
+        return may_be_valid(equation, operators);
+    }
+
+    fn validate(values: &[u64], operators: &[&dyn Operator], target: u64) -> bool {
+        match values {
+            [first] => *first == target,
+            [rest @ .., last] => operators.iter().any(|operator| {
+                operator
+                    .inverse(target, *last)
+                    .is_some_and(|x| validate(rest, operators, x))
+            }),
+            [] => unreachable!(),
+        }
+    }
+
+    validate(&equation.values, operators, equation.result)
+}
+
+/// Call `found` with every sequence of operators giving the result, until it returns false
+fn for_each_solution<'a>(
+    equation: &Equation,
+    operators: &[&'a dyn Operator],
+    found: &mut impl FnMut(&[&'a dyn Operator]) -> bool,
+) {
+    // This method is only valid if all values are positive
+    assert!(!equation.values.contains(&0));
+    assert!(!equation.values.is_empty());
+
+    // Returns false to stop the search
+    fn search<'a>(
+        equation: &Equation,
+        operators: &[&'a dyn Operator],
+        increasing: bool,
+        accumulator: u64,
+        sequence: &mut Vec<&'a dyn Operator>,
+        found: &mut impl FnMut(&[&'a dyn Operator]) -> bool,
+    ) -> bool {
+        let index = sequence.len() + 1;
+        if index == equation.values.len() {
+            return accumulator != equation.result || found(sequence);
+        }
+        if increasing && accumulator > equation.result {
+            // Optimization: values can only grow
+            return true;
         }
+        for &operator in operators {
+            let Some(value) = operator.apply(accumulator, equation.values[index]) else {
+                continue;
+            };
+            sequence.push(operator);
+            let more = search(equation, operators, increasing, value, sequence, found);
+            sequence.pop();
+            if !more {
+                return false;
+            }
+        }
+        true
     }
 
-    validate(equation, operators, 0, 0)
+    let increasing = operators.iter().all(|x| x.is_increasing());
+    search(
+        equation,
+        operators,
+        increasing,
+        equation.values[0],
+        &mut vec![],
+        found,
+    );
+}
+
+/// The first sequence of operators giving the result, trying the operators in the given order
+fn find_solution<'a>(
+    equation: &Equation,
+    operators: &[&'a dyn Operator],
+) -> Option<Vec<&'a dyn Operator>> {
+    let mut solution = None;
+    for_each_solution(equation, operators, &mut |x| {
+        solution = Some(x.to_vec());
+        false
+    });
+    solution
+}
+
+/// All the sequences of operators giving the result
+fn find_all_solutions<'a>(
+    equation: &Equation,
+    operators: &[&'a dyn Operator],
+) -> Vec<Vec<&'a dyn Operator>> {
+    let mut solutions = vec![];
+    for_each_solution(equation, operators, &mut |x| {
+        solutions.push(x.to_vec());
+        true
+    });
+    solutions
 }
 
 fn parse_file(file: &str) -> Vec<Equation> {
@@ -109,7 +328,7 @@ mod tests {
         for (i, equation) in equations.iter().enumerate() {
             let expected_result = valid_equations.contains(&i);
             assert_eq!(
-                may_be_valid(equation, part1_operators),
+                may_be_valid(equation, &PART1_OPERATORS),
                 expected_result
             );
         }
@@ -119,18 +338,204 @@ mod tests {
         for (i, equation) in equations.iter().enumerate() {
             let expected_result = valid_equations.contains(&i);
             assert_eq!(
-                may_be_valid(equation, part2_operators),
+                may_be_valid(equation, &PART2_OPERATORS),
                 expected_result
             );
         }
     }
 
+    #[test]
+    fn solution_tests() {
+        let equations = parse_file(TEST_INPUT);
+        let show = |equation: &Equation, operators: &[&dyn Operator]| {
+            Expression {
+                values: &equation.values,
+                operators,
+            }
+            .to_string()
+        };
+
+        let solutions = find_all_solutions(&equations[1], &PART1_OPERATORS);
+        let expressions: Vec<_> = solutions.iter().map(|x| show(&equations[1], x)).collect();
+        assert_eq!(expressions, vec!["81 + 40 * 27", "81 * 40 + 27"]);
+
+        let solution = find_solution(&equations[4], &PART2_OPERATORS).unwrap();
+        assert_eq!(show(&equations[4], &solution), "6 * 8 || 6 * 15");
+        assert!(find_solution(&equations[4], &PART1_OPERATORS).is_none());
+        assert!(find_solution(&equations[2], &PART2_OPERATORS).is_none());
+
+        // A single value is its own solution
+        let equation = Equation {
+            result: 5,
+            values: vec![5],
+        };
+        let solutions = find_all_solutions(&equation, &PART1_OPERATORS);
+        assert_eq!(solutions.len(), 1);
+        assert!(solutions[0].is_empty());
+        assert_eq!(show(&equation, &[]), "5");
+
+        // Same equations as `may_be_valid`, and every solution checks out
+        for (operators, sum) in [(&PART1_OPERATORS[..], 3749), (&PART2_OPERATORS[..], 11387)] {
+            let mut total = 0;
+            for equation in &equations {
+                let solutions = find_all_solutions(equation, operators);
+                for x in &solutions {
+                    let expression = Expression {
+                        values: &equation.values,
+                        operators: x,
+                    };
+                    assert_eq!(expression.evaluate(), Some(equation.result));
+                }
+                if !solutions.is_empty() {
+                    total += equation.result;
+                }
+            }
+            assert_eq!(total, sum);
+        }
+    }
+
+    #[test]
+    fn backward_tests() {
+        let equations = parse_file(TEST_INPUT);
+        for operators in [&PART1_OPERATORS[..], &PART2_OPERATORS[..]] {
+            for equation in &equations {
+                assert_eq!(
+                    may_be_valid_backward(equation, operators),
+                    may_be_valid(equation, operators)
+                );
+            }
+        }
+        assert_eq!(count_valid_backward(&equations, &PART1_OPERATORS), 3749);
+        assert_eq!(count_valid_backward(&equations, &PART2_OPERATORS), 11387);
+
+        for operator in operators::ALL_OPERATORS {
+            for (a, b) in [(1, 1), (12, 345), (7, 10), (100, 3), (0, 8)] {
+                if let Some(result) = operator.apply(a, b) {
+                    assert_eq!(operator.inverse(result, b), Some(a));
+                }
+            }
+        }
+        assert_eq!(operators::Multiply.inverse(10, 3), None);
+        assert_eq!(operators::Concat.inverse(1234, 35), None);
+        assert_eq!(operators::Concat.inverse(34, 34), Some(0));
+        assert_eq!(operators::Add.inverse(5, 5), Some(0));
+        assert_eq!(operators::Add.inverse(5, 6), None);
+
+        // An operator without an inverse sends the backward solver to the forward search
+        struct Max;
+        impl Operator for Max {
+            fn apply(&self, a: u64, b: u64) -> Option<u64> {
+                Some(a.max(b))
+            }
+
+            fn symbol(&self) -> &'static str {
+                "max"
+            }
+        }
+        assert!(!Max.has_inverse());
+        assert!(operators::ALL_OPERATORS.iter().all(|x| x.has_inverse()));
+        let operators: [&dyn Operator; 2] = [&operators::Add, &Max];
+        for equation in &equations {
+            assert_eq!(
+                may_be_valid_backward(equation, &operators),
+                may_be_valid(equation, &operators)
+            );
+        }
+        let equation = Equation {
+            result: 7,
+            values: vec![2, 3, 7],
+        };
+        assert!(may_be_valid_backward(&equation, &operators));
+        assert!(!may_be_valid_backward(&equation, &PART2_OPERATORS));
+
+        // Floor division maps several left operands to the same result: undoing it with `r * b`
+        // would miss 7 / 2 == 3, so it does not claim an inverse
+        struct Divide;
+        impl Operator for Divide {
+            fn apply(&self, a: u64, b: u64) -> Option<u64> {
+                a.checked_div(b)
+            }
+
+            fn inverse(&self, result: u64, b: u64) -> Option<u64> {
+                result.checked_mul(b)
+            }
+
+            fn symbol(&self) -> &'static str {
+                "/"
+            }
+        }
+        let equation = Equation {
+            result: 3,
+            values: vec![7, 2],
+        };
+        assert!(may_be_valid_backward(
+            &equation,
+            &[&operators::Add, &Divide]
+        ));
+    }
+
+    #[test]
+    fn operator_tests() {
+        use operators::{from_symbol, Add, Concat, Multiply, Subtract, Xor};
+
+        let equation = Equation {
+            result: 5,
+            values: vec![10, 3, 2],
+        };
+        let operators: [&dyn Operator; 2] = [&Add, &Subtract];
+        let solution = find_solution(&equation, &operators).unwrap();
+        let expression = Expression {
+            values: &equation.values,
+            operators: &solution,
+        };
+        assert_eq!(expression.to_string(), "10 - 3 - 2");
+        assert!(may_be_valid(&equation, &operators));
+        assert!(may_be_valid_backward(&equation, &operators));
+        assert!(!may_be_valid(&equation, &PART2_OPERATORS));
+
+        // Subtraction and XOR can make values smaller, so the forward search cannot prune:
+        // both solvers must still agree
+        let equations = parse_file(TEST_INPUT);
+        let operators: [&dyn Operator; 4] = [&Add, &Multiply, &Subtract, &Xor];
+        let valid: Vec<_> = equations
+            .iter()
+            .map(|x| may_be_valid(x, &operators))
+            .collect();
+        for (equation, valid) in equations.iter().zip(valid) {
+            assert_eq!(may_be_valid_backward(equation, &operators), valid);
+            // Adding operators never makes an equation invalid
+            assert!(valid || !may_be_valid(equation, &PART1_OPERATORS));
+        }
+
+        // Parentheses show where left to right differs from the usual precedence
+        let show = |values: &[u64], operators: &[&dyn Operator]| {
+            format!("{:#}", Expression { values, operators })
+        };
+        assert_eq!(show(&[81, 40, 27], &[&Add, &Multiply]), "(81 + 40) * 27");
+        assert_eq!(show(&[81, 40, 27], &[&Multiply, &Add]), "81 * 40 + 27");
+        assert_eq!(
+            show(&[6, 8, 6, 15], &[&Multiply, &Concat, &Multiply]),
+            "(6 * 8) || 6 * 15"
+        );
+        assert_eq!(show(&[1, 2, 3], &[&Subtract, &Add]), "1 - 2 + 3");
+        assert_eq!(show(&[1, 2, 3], &[&Xor, &Add]), "(1 ^ 2) + 3");
+
+        assert_eq!(from_symbol("||").map(|x| x.symbol()), Some("||"));
+        assert!(from_symbol("/").is_none());
+        let overflow = Expression {
+            values: &[u64::MAX, 2],
+            operators: &[&Multiply],
+        };
+        assert_eq!(overflow.evaluate(), None);
+    }
+
     #[test]
     fn concat_tests() {
-        assert_eq!(int_concat(1, 1), 11);
-        assert_eq!(int_concat(1, 9), 19);
-        assert_eq!(int_concat(1, 10), 110);
-        assert_eq!(int_concat(11, 10), 1110);
-        assert_eq!(int_concat(123, 123), 123123);
+        assert_eq!(int_concat(1, 1), Some(11));
+        assert_eq!(int_concat(1, 9), Some(19));
+        assert_eq!(int_concat(1, 10), Some(110));
+        assert_eq!(int_concat(11, 10), Some(1110));
+        assert_eq!(int_concat(123, 123), Some(123123));
+        assert_eq!(int_concat(u64::MAX / 10, 123), None);
     }
 }
@@ -0,0 +1,170 @@
+/// An operator that can appear between two values of an equation.
+/// Equations are always evaluated from left to right; the precedence is only used to show them.
+pub trait Operator {
+    /// `a <op> b`, or `None` if the result does not fit or is not defined
+    fn apply(&self, a: u64, b: u64) -> Option<u64>;
+
+    /// Whether `inverse` is implemented, as needed by the backward solver
+    fn has_inverse(&self) -> bool {
+        false
+    }
+
+    /// The only left operand `a` such that `apply(a, b) == Some(result)`, if there is one.
+    /// This requires a different result for every left operand: otherwise the backward solver
+    /// would have to try every possible `a`, and `has_inverse` must stay false.
+    fn inverse(&self, _result: u64, _b: u64) -> Option<u64> {
+        None
+    }
+
+    /// Whether `apply(a, b) >= a` for positive values, so that the forward search can give up
+    /// once the result is exceeded
+    fn is_increasing(&self) -> bool {
+        false
+    }
+
+    fn symbol(&self) -> &'static str;
+
+    /// How tightly the operator binds in the usual notation, higher first.
+    /// Operators without one are shown as binding tighter than any other.
+    fn precedence(&self) -> Option<u8> {
+        None
+    }
+}
+
+pub struct Add;
+pub struct Multiply;
+pub struct Concat;
+pub struct Subtract;
+pub struct Xor;
+
+pub const PART1_OPERATORS: [&dyn Operator; 2] = [&Add, &Multiply];
+pub const PART2_OPERATORS: [&dyn Operator; 3] = [&Add, &Multiply, &Concat];
+pub const ALL_OPERATORS: [&dyn Operator; 5] = [&Add, &Multiply, &Concat, &Subtract, &Xor];
+
+/// The built-in operator written as `symbol`
+pub fn from_symbol(symbol: &str) -> Option<&'static dyn Operator> {
+    ALL_OPERATORS.into_iter().find(|x| x.symbol() == symbol)
+}
+
+impl Operator for Add {
+    fn apply(&self, a: u64, b: u64) -> Option<u64> {
+        a.checked_add(b)
+    }
+
+    fn has_inverse(&self) -> bool {
+        true
+    }
+
+    fn inverse(&self, result: u64, b: u64) -> Option<u64> {
+        result.checked_sub(b)
+    }
+
+    fn is_increasing(&self) -> bool {
+        true
+    }
+
+    fn symbol(&self) -> &'static str {
+        "+"
+    }
+
+    fn precedence(&self) -> Option<u8> {
+        Some(2)
+    }
+}
+
+impl Operator for Multiply {
+    fn apply(&self, a: u64, b: u64) -> Option<u64> {
+        a.checked_mul(b)
+    }
+
+    fn has_inverse(&self) -> bool {
+        true
+    }
+
+    fn inverse(&self, result: u64, b: u64) -> Option<u64> {
+        (b != 0 && result.is_multiple_of(b)).then(|| result / b)
+    }
+
+    fn is_increasing(&self) -> bool {
+        true
+    }
+
+    fn symbol(&self) -> &'static str {
+        "*"
+    }
+
+    fn precedence(&self) -> Option<u8> {
+        Some(3)
+    }
+}
+
+impl Operator for Concat {
+    fn apply(&self, a: u64, b: u64) -> Option<u64> {
+        crate::int_concat(a, b)
+    }
+
+    fn has_inverse(&self) -> bool {
+        true
+    }
+
+    fn inverse(&self, result: u64, b: u64) -> Option<u64> {
+        // Strip the digits of `b` from the end of `result`
+        let multiplier = 10_u64.checked_pow(b.checked_ilog10().unwrap_or(0) + 1)?;
+        let prefix = result.checked_sub(b)?;
+        prefix
+            .is_multiple_of(multiplier)
+            .then(|| prefix / multiplier)
+    }
+
+    fn is_increasing(&self) -> bool {
+        true
+    }
+
+    fn symbol(&self) -> &'static str {
+        "||"
+    }
+}
+
+impl Operator for Subtract {
+    fn apply(&self, a: u64, b: u64) -> Option<u64> {
+        a.checked_sub(b)
+    }
+
+    fn has_inverse(&self) -> bool {
+        true
+    }
+
+    fn inverse(&self, result: u64, b: u64) -> Option<u64> {
+        result.checked_add(b)
+    }
+
+    fn symbol(&self) -> &'static str {
+        "-"
+    }
+
+    fn precedence(&self) -> Option<u8> {
+        Some(2)
+    }
+}
+
+impl Operator for Xor {
+    fn apply(&self, a: u64, b: u64) -> Option<u64> {
+        Some(a ^ b)
+    }
+
+    fn has_inverse(&self) -> bool {
+        true
+    }
+
+    fn inverse(&self, result: u64, b: u64) -> Option<u64> {
+        Some(result ^ b)
+    }
+
+    fn symbol(&self) -> &'static str {
+        "^"
+    }
+
+    fn precedence(&self) -> Option<u8> {
+        Some(1)
+    }
+}